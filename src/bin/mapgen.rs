@@ -0,0 +1,449 @@
+#![allow(clippy::needless_return)]
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+    time::Instant,
+};
+
+use voronoi_map_gen::{
+    config::config::GenerationConfig,
+    error::error::MapGenError,
+    export::{
+        geojson::geojson::save_geojson,
+        heightmap::heightmap::render_heightmap,
+        mesh::mesh::{save_mesh, MeshFormat},
+        raster::raster::save_png,
+        svg::svg::save_svg,
+    },
+    graph2::graph2::{Biome, Graph},
+    terrain2::{report::report::GenerationReport, terrain2::full_terrain_gen},
+};
+
+const USAGE: &str = "\
+Generates maps without opening the viewer.
+
+Usage: mapgen [options]
+
+Options:
+  --seed <n>            Seed of the first map, random if left out
+  --count <n>           Number of maps to generate, seeded seed, seed + 1, ... [default: 1]
+  --cells <n>           Number of cells in each map
+  --size <w>x<h>        Map bounds, x_scale by y_scale
+  --config <file>       GenerationConfig file of `field = value` lines,
+                        --cells and --size take precedence over it
+  --format <list>       Comma separated outputs [default: png]
+                          png, svg, geojson, heightmap (16 bit png), r16,
+                          obj, ply, glb, map (a file for Graph::load)
+  --image-size <w>x<h>  Pixel size of png, heightmap and r16 outputs [default: map size]
+  --vertical-scale <n>  Height of elevation 1 in mesh outputs [default: 100]
+  --labels              Draw biome labels in svg output
+  --out <dir>           Directory to write outputs to [default: .]
+  --summary <file>      CSV of per map statistics [default: <out>/summary.csv when --count > 1]
+  --threads <n>         Maps generated at once [default: available cores]
+  -h, --help            Print this message
+";
+
+const FORMATS: [&str; 9] = [
+    "png",
+    "svg",
+    "geojson",
+    "heightmap",
+    "r16",
+    "obj",
+    "ply",
+    "glb",
+    "map",
+];
+
+struct Options {
+    seed: u64,
+    count: usize,
+    config: GenerationConfig,
+    formats: Vec<String>,
+    image_size: Option<(u32, u32)>,
+    vertical_scale: f32,
+    labels: bool,
+    out: PathBuf,
+    summary: Option<PathBuf>,
+    threads: usize,
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    return value
+        .parse()
+        .map_err(|_| format!("`{}` is not a valid value for {}", value, flag));
+}
+
+fn parse_size<T: std::str::FromStr>(flag: &str, value: &str) -> Result<(T, T), String> {
+    let (width, height) = value
+        .split_once('x')
+        .ok_or_else(|| format!("{} expects <width>x<height>, got `{}`", flag, value))?;
+    return Ok((parse_number(flag, width)?, parse_number(flag, height)?));
+}
+
+fn parse_args(args: Vec<String>) -> Result<Option<Options>, String> {
+    let mut seed: Option<u64> = None;
+    let mut count: usize = 1;
+    let mut cells: Option<usize> = None;
+    let mut size: Option<(f64, f64)> = None;
+    let mut config_path: Option<PathBuf> = None;
+    let mut formats: Vec<String> = vec![String::from("png")];
+    let mut image_size: Option<(u32, u32)> = None;
+    let mut vertical_scale: f32 = 100.0;
+    let mut labels = false;
+    let mut out = PathBuf::from(".");
+    let mut summary: Option<PathBuf> = None;
+    let mut threads: Option<usize> = None;
+
+    let mut args = args.into_iter();
+    while let Some(flag) = args.next() {
+        if flag == "-h" || flag == "--help" {
+            return Ok(None);
+        }
+        if flag == "--labels" {
+            labels = true;
+            continue;
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| format!("{} expects a value", flag))?;
+        match flag.as_str() {
+            "--seed" => seed = Some(parse_number(&flag, &value)?),
+            "--count" => count = parse_number(&flag, &value)?,
+            "--cells" => cells = Some(parse_number(&flag, &value)?),
+            "--size" => size = Some(parse_size(&flag, &value)?),
+            "--config" => config_path = Some(PathBuf::from(value)),
+            "--format" => {
+                formats = value.split(',').map(|f| f.trim().to_lowercase()).collect();
+                if let Some(unknown) = formats.iter().find(|f| !FORMATS.contains(&f.as_str())) {
+                    return Err(format!("unknown format `{}`", unknown));
+                }
+            }
+            "--image-size" => image_size = Some(parse_size(&flag, &value)?),
+            "--vertical-scale" => vertical_scale = parse_number(&flag, &value)?,
+            "--out" => out = PathBuf::from(value),
+            "--summary" => summary = Some(PathBuf::from(value)),
+            "--threads" => threads = Some(parse_number(&flag, &value)?),
+            _ => return Err(format!("unknown option `{}`", flag)),
+        }
+    }
+
+    let mut config = match config_path {
+        Some(path) => GenerationConfig::load(path).map_err(|e| e.to_string())?,
+        None => GenerationConfig::default(),
+    };
+    if let Some(cells) = cells {
+        config.cell_count = cells;
+    }
+    if let Some((x_scale, y_scale)) = size {
+        config.x_scale = x_scale;
+        config.y_scale = y_scale;
+    }
+    config.validate().map_err(|e| e.to_string())?;
+    if count == 0 {
+        return Err(String::from("--count must be at least 1"));
+    }
+    if summary.is_none() && count > 1 {
+        summary = Some(out.join("summary.csv"));
+    }
+    let threads = threads
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()))
+        .clamp(1, count);
+
+    return Ok(Some(Options {
+        seed: seed.unwrap_or_else(rand::random::<u64>),
+        count,
+        config,
+        formats,
+        image_size,
+        vertical_scale,
+        labels,
+        out,
+        summary,
+        threads,
+    }));
+}
+
+/// One row of the summary csv
+struct MapSummary {
+    seed: u64,
+    milliseconds: u128,
+    outcome: Result<GenerationReport, MapGenError>,
+}
+
+const SUMMARY_COLUMNS: [&str; 12] = [
+    "generation_ms",
+    "cells",
+    "edges",
+    "corners",
+    "land_ratio",
+    "ocean_ratio",
+    "lake_ratio",
+    "river_count",
+    "river_length",
+    "relaxation_passes",
+    "area_variance",
+    "error",
+];
+
+// the fixed columns, then a count of cells for each biome
+fn summary_header() -> String {
+    let mut columns: Vec<String> = vec![String::from("seed"), String::from("milliseconds")];
+    columns.extend(SUMMARY_COLUMNS.iter().map(|c| String::from(*c)));
+    columns.extend(Biome::ALL.iter().map(|b| format!("{:?}", b)));
+    return columns.join(",");
+}
+
+impl MapSummary {
+    fn csv_row(&self) -> String {
+        let mut columns: Vec<String> = vec![self.seed.to_string(), self.milliseconds.to_string()];
+        match &self.outcome {
+            Ok(report) => {
+                columns.extend([
+                    report.total_duration().as_millis().to_string(),
+                    report.cell_count.to_string(),
+                    report.edge_count.to_string(),
+                    report.corner_count.to_string(),
+                    format!("{:.4}", report.land_ratio),
+                    format!("{:.4}", report.ocean_ratio),
+                    format!("{:.4}", report.lake_ratio),
+                    report.river_count.to_string(),
+                    format!("{:.1}", report.river_length),
+                    report.relaxation.iterations.to_string(),
+                    format!("{:.4}", report.relaxation.area_variance),
+                    String::new(),
+                ]);
+                columns.extend(report.biome_histogram.iter().map(|(_, n)| n.to_string()));
+            }
+            Err(e) => {
+                columns.extend(vec![String::new(); SUMMARY_COLUMNS.len() - 1]);
+                // quote the message, it may hold commas
+                columns.push(format!("\"{}\"", e.to_string().replace('"', "\"\"")));
+                columns.extend(vec![String::new(); Biome::ALL.len()]);
+            }
+        }
+        return columns.join(",");
+    }
+}
+
+fn write_outputs(graph: &Graph, options: &Options, seed: u64) -> Result<(), MapGenError> {
+    let (width, height) = options
+        .image_size
+        .unwrap_or((graph.x_scale as u32, graph.y_scale as u32));
+    let path = |extension: &str| options.out.join(format!("map-{}{}", seed, extension));
+    for format in &options.formats {
+        match format.as_str() {
+            "png" => save_png(graph, path(".png"), width, height)?,
+            "svg" => save_svg(graph, path(".svg"), options.labels)?,
+            "geojson" => save_geojson(graph, path(".geojson"))?,
+            "heightmap" => render_heightmap(graph, width, height)?.save_png(path("-height.png"))?,
+            "r16" => render_heightmap(graph, width, height)?.save_r16(path(".r16"))?,
+            "obj" => save_mesh(graph, path(".obj"), MeshFormat::Obj, options.vertical_scale)?,
+            "ply" => save_mesh(graph, path(".ply"), MeshFormat::Ply, options.vertical_scale)?,
+            "glb" => save_mesh(
+                graph,
+                path(".glb"),
+                MeshFormat::Gltf,
+                options.vertical_scale,
+            )?,
+            "map" => graph.save(path(".vmap"))?,
+            _ => unreachable!("formats are checked in parse_args"),
+        }
+    }
+    return Ok(());
+}
+
+fn generate(options: &Options, seed: u64) -> MapSummary {
+    let start = Instant::now();
+    let outcome = full_terrain_gen(&options.config, seed).and_then(|(graph, report)| {
+        write_outputs(&graph, options, seed)?;
+        return Ok(report);
+    });
+    return MapSummary {
+        seed,
+        milliseconds: start.elapsed().as_millis(),
+        outcome,
+    };
+}
+
+// maps are handed out one at a time, so a slow seed doesn't hold up a
+// whole batch of others
+fn generate_all(options: &Options) -> Vec<MapSummary> {
+    let next = AtomicUsize::new(0);
+    let summaries: Mutex<Vec<(usize, MapSummary)>> = Mutex::new(Vec::new());
+    thread::scope(|scope| {
+        for _ in 0..options.threads {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                if index >= options.count {
+                    break;
+                }
+                let seed = options.seed.wrapping_add(index as u64);
+                let summary = generate(options, seed);
+                match &summary.outcome {
+                    Ok(_) => eprintln!("seed {}: done in {}ms", seed, summary.milliseconds),
+                    Err(e) => eprintln!("seed {}: failed: {}", seed, e),
+                }
+                summaries.lock().unwrap().push((index, summary));
+            });
+        }
+    });
+    let mut summaries = summaries.into_inner().unwrap();
+    summaries.sort_by_key(|(index, _)| *index);
+    return summaries.into_iter().map(|(_, summary)| summary).collect();
+}
+
+fn write_summary(path: &Path, summaries: &[MapSummary]) -> Result<(), MapGenError> {
+    let mut csv = format!("{}\n", summary_header());
+    for summary in summaries {
+        csv.push_str(&summary.csv_row());
+        csv.push('\n');
+    }
+    return fs::write(path, csv).map_err(|e| MapGenError::Io(e.to_string()));
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(env::args().skip(1).collect()) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            print!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("mapgen: {}\n\n{}", e, USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Err(e) = fs::create_dir_all(&options.out) {
+        eprintln!("mapgen: could not create {}: {}", options.out.display(), e);
+        return ExitCode::FAILURE;
+    }
+
+    let summaries = generate_all(&options);
+    if let Some(path) = &options.summary {
+        if let Err(e) = write_summary(path, &summaries) {
+            eprintln!("mapgen: could not write {}: {}", path.display(), e);
+            return ExitCode::FAILURE;
+        }
+    }
+    let failures = summaries.iter().filter(|s| s.outcome.is_err()).count();
+    if failures > 0 {
+        eprintln!("mapgen: {} of {} maps failed", failures, summaries.len());
+        return ExitCode::FAILURE;
+    }
+    return ExitCode::SUCCESS;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(text: &str) -> Vec<String> {
+        return text.split_whitespace().map(String::from).collect();
+    }
+
+    fn parse(text: &str) -> Options {
+        return parse_args(args(text)).unwrap().unwrap();
+    }
+
+    // counts columns the way a csv reader would, ignoring commas in quotes
+    fn csv_columns(row: &str) -> usize {
+        let mut quoted = false;
+        let mut columns = 1;
+        for c in row.chars() {
+            match c {
+                '"' => quoted = !quoted,
+                ',' if !quoted => columns += 1,
+                _ => {}
+            }
+        }
+        return columns;
+    }
+
+    #[test]
+    fn flags_override_config_file_test() {
+        let path = env::temp_dir().join(format!("mapgen-test-{}.cfg", std::process::id()));
+        fs::write(
+            &path,
+            "cell_count = 700\nx_scale = 300\ny_scale = 150\nrelax_factor = 2\n",
+        )
+        .unwrap();
+        let config_arg = format!("--config {}", path.display());
+
+        let from_file = parse(&config_arg);
+        assert_eq!(from_file.config.cell_count, 700);
+        assert_eq!(from_file.config.x_scale, 300.0);
+
+        let overridden = parse(&format!("{} --cells 900 --size 400x200", config_arg));
+        assert_eq!(overridden.config.cell_count, 900);
+        assert_eq!(overridden.config.x_scale, 400.0);
+        assert_eq!(overridden.config.y_scale, 200.0);
+        // values only the file sets are kept
+        assert_eq!(overridden.config.relax_factor, 2);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn rejects_bad_arguments_test() {
+        assert!(parse_args(args("--format png,tiff"))
+            .err()
+            .unwrap()
+            .contains("tiff"));
+        assert!(parse_args(args("--count 0")).is_err());
+        assert!(parse_args(args("--size 100")).is_err());
+        assert!(parse_args(args("--seed")).is_err());
+        assert!(parse_args(args("--colour red")).is_err());
+        assert!(parse_args(args("--help")).unwrap().is_none());
+    }
+
+    #[test]
+    fn summary_and_thread_defaults_test() {
+        let single = parse("--seed 1 --out maps");
+        assert_eq!(single.summary, None);
+        let batch = parse("--seed 1 --count 3 --out maps");
+        assert_eq!(
+            batch.summary,
+            Some(PathBuf::from("maps").join("summary.csv"))
+        );
+        let named = parse("--count 3 --summary stats.csv");
+        assert_eq!(named.summary, Some(PathBuf::from("stats.csv")));
+
+        assert_eq!(parse("--count 2 --threads 8").threads, 2);
+        assert_eq!(parse("--count 4 --threads 0").threads, 1);
+        assert!((1..=5).contains(&parse("--count 5").threads));
+    }
+
+    #[test]
+    fn csv_rows_match_header_test() {
+        let config = GenerationConfig {
+            cell_count: 200,
+            ..Default::default()
+        };
+        let header_columns = csv_columns(&summary_header());
+
+        let ok = MapSummary {
+            seed: 3,
+            milliseconds: 10,
+            outcome: full_terrain_gen(&config, 3).map(|(_graph, report)| report),
+        };
+        assert!(ok.outcome.is_ok());
+        assert_eq!(csv_columns(&ok.csv_row()), header_columns);
+
+        let err = MapSummary {
+            seed: 4,
+            milliseconds: 10,
+            outcome: Err(MapGenError::InvalidConfig(String::from(
+                "bad, \"quoted\" value",
+            ))),
+        };
+        let row = err.csv_row();
+        assert_eq!(csv_columns(&row), header_columns);
+        assert!(row.contains("\"invalid config: bad, \"\"quoted\"\" value\""));
+    }
+}
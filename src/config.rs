@@ -0,0 +1,333 @@
+pub mod config {
+    use std::{fs, path::Path, str::FromStr};
+
+    use crate::{
+        error::error::MapGenError, graph2::mask::mask::BoundaryMask,
+        voronoi::voronoi::PointDistribution,
+    };
+
+    /// All tunable parameters for a single map generation run.
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct GenerationConfig {
+        // Base Graph
+        /// Number of voronoi sites, and therefore cells, in the map
+        pub cell_count: usize,
+        pub x_scale: f64,
+        pub y_scale: f64,
+        /// Maximum number of Lloyd relaxation passes applied to the initial points
+        pub relax_factor: usize,
+        /// Relaxation stops once no site moves further than this fraction of
+        /// the mean site spacing in a pass, 0 always runs every pass
+        pub relax_tolerance: f64,
+        /// How the voronoi sites are placed before relaxation
+        pub point_distribution: PointDistribution,
+        /// Joins the east and west edges of the map, making a cylindrical world
+        pub wrap_x: bool,
+        /// Builds a planet instead of a flat map, see `generate_sphere_graph`
+        pub sphere: bool,
+        /// Region of the bounds to generate land in, cells outside it become ocean
+        pub boundary: BoundaryMask,
+        // Island
+        pub noise_scale: f32,
+        pub water_coverage_modifier: f64,
+        // Rivers
+        pub peak_rainwater_collection_ratio: f32,
+        pub peak_elev_max: f32,
+        pub peak_elev_min: f32,
+        pub mid_rainwater_collection_ratio: f32,
+        pub mid_elev_max: f32,
+        pub mid_elev_min: f32,
+        // Biomes
+        pub moisture_falloff: f32,
+        /// Extra distance, in map units, moisture travels for each unit of
+        /// elevation it climbs or descends along an edge. 0 spreads it by
+        /// distance alone, higher values let ridges hold it back
+        pub moisture_elevation_weight: f32,
+        // Edge Detail
+        /// Number of times each edge is halved when adding midpoints
+        pub edge_subdivisions: usize,
+    }
+
+    impl Default for GenerationConfig {
+        fn default() -> Self {
+            return GenerationConfig {
+                cell_count: 2000,
+                x_scale: 1600.0,
+                y_scale: 800.0,
+                relax_factor: 5,
+                relax_tolerance: 0.01,
+                point_distribution: PointDistribution::Uniform,
+                wrap_x: false,
+                sphere: false,
+                boundary: BoundaryMask::Rectangle,
+                noise_scale: 4.0,
+                water_coverage_modifier: 1.0,
+                peak_rainwater_collection_ratio: 0.25,
+                peak_elev_max: 0.9,
+                peak_elev_min: 0.75,
+                mid_rainwater_collection_ratio: 0.03,
+                mid_elev_max: 0.75,
+                mid_elev_min: 0.33,
+                moisture_falloff: 0.05,
+                moisture_elevation_weight: 0.0,
+                edge_subdivisions: 2,
+            };
+        }
+    }
+
+    fn check(valid: bool, reason: &str) -> Result<(), MapGenError> {
+        if valid {
+            return Ok(());
+        }
+        return Err(MapGenError::InvalidConfig(String::from(reason)));
+    }
+
+    fn is_ratio(value: f32) -> bool {
+        return (0.0..=1.0).contains(&value);
+    }
+
+    impl GenerationConfig {
+        /// Rejects parameters that would otherwise panic or produce an
+        /// unusable map part way through generation.
+        pub fn validate(&self) -> Result<(), MapGenError> {
+            match &self.point_distribution {
+                PointDistribution::Custom(points) => {
+                    check(points.len() >= 3, "at least 3 custom points are required")?;
+                    check(
+                        points.iter().all(|(x, y)| {
+                            (0.0..=self.x_scale).contains(x) && (0.0..=self.y_scale).contains(y)
+                        }),
+                        "custom points must lie within x_scale and y_scale",
+                    )?;
+                }
+                PointDistribution::JitteredGrid { jitter } => {
+                    check(self.cell_count >= 3, "cell_count must be at least 3")?;
+                    check(
+                        *jitter > 0.0 && *jitter <= 1.0,
+                        "grid jitter must be in (0, 1]",
+                    )?;
+                }
+                _ => check(self.cell_count >= 3, "cell_count must be at least 3")?,
+            }
+            check(
+                self.x_scale.is_finite() && self.x_scale > 0.0,
+                "x_scale must be positive",
+            )?;
+            check(
+                self.y_scale.is_finite() && self.y_scale > 0.0,
+                "y_scale must be positive",
+            )?;
+            check(
+                !self.sphere || self.point_distribution == PointDistribution::Uniform,
+                "spherical maps only support the uniform point distribution",
+            )?;
+            match &self.boundary {
+                BoundaryMask::Rectangle => {}
+                BoundaryMask::Polygon(points) => {
+                    check(
+                        points.len() >= 3,
+                        "a boundary polygon needs at least 3 points",
+                    )?;
+                }
+                BoundaryMask::Image {
+                    width,
+                    height,
+                    pixels,
+                    threshold: _,
+                } => {
+                    check(
+                        *width > 0 && *height > 0 && pixels.len() == width * height,
+                        "a boundary image must have width * height pixels",
+                    )?;
+                }
+            }
+            check(
+                self.relax_tolerance.is_finite() && self.relax_tolerance >= 0.0,
+                "relax_tolerance must not be negative",
+            )?;
+            check(
+                self.noise_scale.is_finite() && self.noise_scale > 0.0,
+                "noise_scale must be positive",
+            )?;
+            check(
+                self.water_coverage_modifier.is_finite() && self.water_coverage_modifier > 0.0,
+                "water_coverage_modifier must be positive",
+            )?;
+            check(
+                is_ratio(self.peak_rainwater_collection_ratio)
+                    && is_ratio(self.mid_rainwater_collection_ratio),
+                "rainwater collection ratios must be between 0 and 1",
+            )?;
+            check(
+                self.peak_elev_min <= self.peak_elev_max && self.mid_elev_min <= self.mid_elev_max,
+                "river elevation minimums must not exceed their maximums",
+            )?;
+            check(
+                self.moisture_falloff > 0.0 && self.moisture_falloff <= 1.0,
+                "moisture_falloff must be in (0, 1]",
+            )?;
+            check(
+                self.moisture_elevation_weight.is_finite() && self.moisture_elevation_weight >= 0.0,
+                "moisture_elevation_weight must not be negative",
+            )?;
+            check(
+                self.edge_subdivisions <= 8,
+                "edge_subdivisions above 8 produce 256+ points per edge",
+            )?;
+            return Ok(());
+        }
+
+        /// Reads a config file of `field = value` lines, using the field names
+        /// of this struct. Fields left out keep their default, `#` starts a
+        /// comment. `point_distribution` is one of `uniform`, `poisson_disk`,
+        /// `hex_grid` or `jittered_grid <jitter>`, and `boundary` is either
+        /// `rectangle` or `polygon x y, x y, ...` in map coordinates.
+        pub fn parse(text: &str) -> Result<GenerationConfig, MapGenError> {
+            let mut config = GenerationConfig::default();
+            for (line_index, line) in text.lines().enumerate() {
+                let line = line.split('#').next().unwrap_or("").trim();
+                if line.is_empty() {
+                    continue;
+                }
+                let invalid = |reason: &str| {
+                    return MapGenError::InvalidConfig(format!(
+                        "line {}: {}",
+                        line_index + 1,
+                        reason
+                    ));
+                };
+                let (key, value) = line
+                    .split_once('=')
+                    .ok_or_else(|| invalid("expected `field = value`"))?;
+                let (key, value) = (key.trim(), value.trim());
+                config
+                    .set_field(key, value)
+                    .map_err(|reason| invalid(&reason))?;
+            }
+            return Ok(config);
+        }
+
+        pub fn load(path: impl AsRef<Path>) -> Result<GenerationConfig, MapGenError> {
+            let text = fs::read_to_string(path).map_err(|e| MapGenError::Io(e.to_string()))?;
+            return GenerationConfig::parse(&text);
+        }
+
+        pub(crate) fn set_field(&mut self, key: &str, value: &str) -> Result<(), String> {
+            match key {
+                "cell_count" => self.cell_count = parse_value(key, value)?,
+                "x_scale" => self.x_scale = parse_value(key, value)?,
+                "y_scale" => self.y_scale = parse_value(key, value)?,
+                "relax_factor" => self.relax_factor = parse_value(key, value)?,
+                "relax_tolerance" => self.relax_tolerance = parse_value(key, value)?,
+                "point_distribution" => self.point_distribution = parse_point_distribution(value)?,
+                "wrap_x" => self.wrap_x = parse_value(key, value)?,
+                "sphere" => self.sphere = parse_value(key, value)?,
+                "boundary" => self.boundary = parse_boundary(value)?,
+                "noise_scale" => self.noise_scale = parse_value(key, value)?,
+                "water_coverage_modifier" => {
+                    self.water_coverage_modifier = parse_value(key, value)?
+                }
+                "peak_rainwater_collection_ratio" => {
+                    self.peak_rainwater_collection_ratio = parse_value(key, value)?
+                }
+                "peak_elev_max" => self.peak_elev_max = parse_value(key, value)?,
+                "peak_elev_min" => self.peak_elev_min = parse_value(key, value)?,
+                "mid_rainwater_collection_ratio" => {
+                    self.mid_rainwater_collection_ratio = parse_value(key, value)?
+                }
+                "mid_elev_max" => self.mid_elev_max = parse_value(key, value)?,
+                "mid_elev_min" => self.mid_elev_min = parse_value(key, value)?,
+                "moisture_falloff" => self.moisture_falloff = parse_value(key, value)?,
+                "moisture_elevation_weight" => {
+                    self.moisture_elevation_weight = parse_value(key, value)?
+                }
+                "edge_subdivisions" => self.edge_subdivisions = parse_value(key, value)?,
+                _ => return Err(format!("unknown field `{}`", key)),
+            }
+            return Ok(());
+        }
+    }
+
+    fn parse_value<T: FromStr>(key: &str, value: &str) -> Result<T, String> {
+        return value
+            .parse()
+            .map_err(|_| format!("`{}` is not a valid value for {}", value, key));
+    }
+
+    fn parse_point_distribution(value: &str) -> Result<PointDistribution, String> {
+        let mut words = value.split_whitespace();
+        let distribution = match (words.next(), words.next()) {
+            (Some("uniform"), None) => PointDistribution::Uniform,
+            (Some("poisson_disk"), None) => PointDistribution::PoissonDisk,
+            (Some("hex_grid"), None) => PointDistribution::HexGrid,
+            (Some("jittered_grid"), Some(jitter)) => PointDistribution::JitteredGrid {
+                jitter: parse_value("jittered_grid", jitter)?,
+            },
+            _ => return Err(format!("unknown point_distribution `{}`", value)),
+        };
+        if words.next().is_some() {
+            return Err(format!("unknown point_distribution `{}`", value));
+        }
+        return Ok(distribution);
+    }
+
+    fn parse_boundary(value: &str) -> Result<BoundaryMask, String> {
+        if value == "rectangle" {
+            return Ok(BoundaryMask::Rectangle);
+        }
+        let points = value
+            .strip_prefix("polygon")
+            .ok_or_else(|| format!("unknown boundary `{}`", value))?;
+        let mut outline: Vec<(f64, f64)> = Vec::new();
+        for point in points.split(',') {
+            let coordinates: Vec<&str> = point.split_whitespace().collect();
+            match coordinates[..] {
+                [x, y] => outline.push((parse_value("boundary", x)?, parse_value("boundary", y)?)),
+                _ => return Err(format!("`{}` is not an `x y` point", point.trim())),
+            }
+        }
+        return Ok(BoundaryMask::Polygon(outline));
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn parse_config_test() {
+            let config = GenerationConfig::parse(
+                "# a small wrapping map\n\
+                 cell_count = 500\n\
+                 x_scale = 1000   # wider\n\
+                 wrap_x = true\n\
+                 point_distribution = jittered_grid 0.5\n\
+                 boundary = polygon 0 0, 1000 0, 500 800\n",
+            )
+            .unwrap();
+            assert_eq!(config.cell_count, 500);
+            assert_eq!(config.x_scale, 1000.0);
+            assert!(config.wrap_x);
+            assert_eq!(
+                config.point_distribution,
+                PointDistribution::JitteredGrid { jitter: 0.5 }
+            );
+            assert_eq!(
+                config.boundary,
+                BoundaryMask::Polygon(vec![(0.0, 0.0), (1000.0, 0.0), (500.0, 800.0)])
+            );
+            assert_eq!(config.y_scale, GenerationConfig::default().y_scale);
+
+            for text in [
+                "cell_count = many",
+                "colour = blue",
+                "wrap_x",
+                "boundary = circle",
+            ] {
+                assert!(matches!(
+                    GenerationConfig::parse(text),
+                    Err(MapGenError::InvalidConfig(_))
+                ));
+            }
+        }
+    }
+}
@@ -0,0 +1,56 @@
+pub mod error {
+    use std::fmt;
+
+    use crate::graph2::{
+        graph2::{CellId, EdgeId},
+        validation::validation::GraphViolation,
+    };
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum MapGenError {
+        /// A `GenerationConfig` value is out of its usable range
+        InvalidConfig(String),
+        /// The voronoi diagram could not be built from the generated sites
+        DegeneratePoints(String),
+        /// The base graph broke one or more structural invariants
+        InvalidGraph(Vec<GraphViolation>),
+        /// A cell's edges could not be walked into a closed ring
+        BrokenCellRing(CellId),
+        /// An edge had a NaN or infinite elevation
+        NonFiniteElevation(EdgeId),
+        /// A NaN or infinite value was about to be exported
+        NonFiniteValue(String),
+        /// A saved map could not be decoded, see `Graph::from_bytes`
+        InvalidMapFile(String),
+        /// Reading or writing a file failed
+        Io(String),
+    }
+
+    impl fmt::Display for MapGenError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            return match self {
+                MapGenError::InvalidConfig(reason) => write!(f, "invalid config: {}", reason),
+                MapGenError::DegeneratePoints(reason) => {
+                    write!(f, "could not build voronoi diagram: {}", reason)
+                }
+                MapGenError::InvalidGraph(violations) => write!(
+                    f,
+                    "generated graph has {} invariant violations, first: {:?}",
+                    violations.len(),
+                    violations.first()
+                ),
+                MapGenError::BrokenCellRing(cell_id) => {
+                    write!(f, "cell {:?} does not form a closed ring", cell_id)
+                }
+                MapGenError::NonFiniteElevation(edge_id) => {
+                    write!(f, "edge {:?} has a non-finite elevation", edge_id)
+                }
+                MapGenError::NonFiniteValue(reason) => write!(f, "non-finite value: {}", reason),
+                MapGenError::InvalidMapFile(reason) => write!(f, "invalid map file: {}", reason),
+                MapGenError::Io(reason) => write!(f, "io error: {}", reason),
+            };
+        }
+    }
+
+    impl std::error::Error for MapGenError {}
+}
@@ -0,0 +1,205 @@
+pub mod geojson;
+pub mod heightmap;
+pub mod mesh;
+pub mod raster;
+pub mod svg;
+pub mod export {
+    use std::collections::{BTreeSet, HashMap, HashSet};
+
+    use crate::graph2::graph2::{CornerId, EdgeId, Graph};
+
+    /// A run of river edges followed downstream, through each edge's midpoints
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct RiverPath {
+        pub points: Vec<(f32, f32)>,
+        /// Largest volume along the run, where it meets the next river or the sea
+        pub volume: f32,
+        pub edges: Vec<EdgeId>,
+    }
+
+    // joins the paths end to end, dropping the point each shares with the last
+    fn append_path(points: &mut Vec<(f32, f32)>, path: Vec<(f32, f32)>) {
+        let skip = if points.is_empty() { 0 } else { 1 };
+        points.extend(path.into_iter().skip(skip));
+    }
+
+    /// Chains river edges into downstream runs. A run starts at a source, or
+    /// where a smaller stream splits off, and ends at the sea, a lake, or where
+    /// it joins a run already walked. Every river edge is in exactly one run.
+    pub fn river_paths(graph: &Graph) -> Vec<RiverPath> {
+        let river_edges: Vec<EdgeId> = graph
+            .edge_ids()
+            .filter(|id| graph[*id].river > 0.0)
+            .collect();
+        let mut outflows: HashMap<CornerId, Vec<EdgeId>> = HashMap::new();
+        let mut inflow_corners: HashSet<CornerId> = HashSet::new();
+        for edge_id in &river_edges {
+            let (down_corner_id, _down_corner) = graph.get_edge_downwards_corner(*edge_id);
+            let up_corner_id = graph.get_edge_other_corner(*edge_id, down_corner_id);
+            outflows.entry(up_corner_id).or_default().push(*edge_id);
+            inflow_corners.insert(down_corner_id);
+        }
+
+        let upper_corner = |edge_id: EdgeId| {
+            let (down_corner_id, _down_corner) = graph.get_edge_downwards_corner(edge_id);
+            return graph.get_edge_other_corner(edge_id, down_corner_id);
+        };
+        // sources first, so runs are as long as possible
+        let (sources, others): (Vec<EdgeId>, Vec<EdgeId>) = river_edges
+            .iter()
+            .partition(|id| !inflow_corners.contains(&upper_corner(**id)));
+
+        let mut walked: HashSet<EdgeId> = HashSet::new();
+        let mut output: Vec<RiverPath> = Vec::new();
+        for start in sources.into_iter().chain(others) {
+            if walked.contains(&start) {
+                continue;
+            }
+            let mut path = RiverPath {
+                points: Vec::new(),
+                volume: 0.0,
+                edges: Vec::new(),
+            };
+            let mut edge_id = start;
+            loop {
+                walked.insert(edge_id);
+                let up_corner_id = upper_corner(edge_id);
+                append_path(
+                    &mut path.points,
+                    graph.get_edge_path_from(edge_id, up_corner_id),
+                );
+                path.volume = path.volume.max(graph[edge_id].river);
+                path.edges.push(edge_id);
+
+                let down_corner_id = graph.get_edge_other_corner(edge_id, up_corner_id);
+                // follow the largest stream leaving the corner
+                let next = outflows.get(&down_corner_id).and_then(|edge_ids| {
+                    return edge_ids
+                        .iter()
+                        .filter(|id| !walked.contains(id))
+                        .max_by(|a, b| graph[**a].river.total_cmp(&graph[**b].river));
+                });
+                match next {
+                    Some(next_id) => edge_id = *next_id,
+                    None => break,
+                }
+            }
+            path.points = graph.unwrap_path(path.points);
+            output.push(path);
+        }
+        return output;
+    }
+
+    // walks unvisited edges from `corner` until none are left to follow,
+    // returning each edge with the corner it was entered from
+    fn walk_edges(
+        graph: &Graph,
+        mut corner_id: CornerId,
+        remaining: &mut BTreeSet<EdgeId>,
+    ) -> Vec<(EdgeId, CornerId)> {
+        let mut output: Vec<(EdgeId, CornerId)> = Vec::new();
+        while let Some(edge_id) = graph[corner_id]
+            .edges
+            .iter()
+            .copied()
+            .find(|id| remaining.contains(id))
+        {
+            remaining.remove(&edge_id);
+            output.push((edge_id, corner_id));
+            corner_id = graph.get_edge_other_corner(edge_id, corner_id);
+        }
+        return output;
+    }
+
+    /// Chains the edges between land and water, see `Graph::edge_is_coastal`,
+    /// into connected lines. Closed coastlines start and end on the same point.
+    pub fn coastline_paths(graph: &Graph) -> Vec<Vec<(f32, f32)>> {
+        return chain_edges(
+            graph,
+            graph
+                .edge_ids()
+                .filter(|id| graph.edge_is_coastal(*id))
+                .collect(),
+        );
+    }
+
+    /// `coastline_paths` without the coastal edges that carry a river, which
+    /// `renderer2` draws as rivers instead
+    pub fn drawn_coastline_paths(graph: &Graph) -> Vec<Vec<(f32, f32)>> {
+        return chain_edges(
+            graph,
+            graph
+                .edge_ids()
+                .filter(|id| graph.edge_is_coastal(*id) && graph[*id].river <= 0.0)
+                .collect(),
+        );
+    }
+
+    fn chain_edges(graph: &Graph, mut remaining: BTreeSet<EdgeId>) -> Vec<Vec<(f32, f32)>> {
+        let mut output: Vec<Vec<(f32, f32)>> = Vec::new();
+        while let Some(first_edge_id) = remaining.pop_first() {
+            let (c_1, c_2) = graph[first_edge_id].corners;
+            let forwards = walk_edges(graph, c_2, &mut remaining);
+            let backwards = walk_edges(graph, c_1, &mut remaining);
+
+            let mut points: Vec<(f32, f32)> = Vec::new();
+            for (edge_id, from) in backwards.iter().rev() {
+                let far_corner = graph.get_edge_other_corner(*edge_id, *from);
+                append_path(&mut points, graph.get_edge_path_from(*edge_id, far_corner));
+            }
+            append_path(&mut points, graph.get_edge_path_from(first_edge_id, c_1));
+            for (edge_id, from) in forwards {
+                append_path(&mut points, graph.get_edge_path_from(edge_id, from));
+            }
+            output.push(graph.unwrap_path(points));
+        }
+        return output;
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::{config::config::GenerationConfig, terrain2::terrain2::full_terrain_gen};
+
+        #[test]
+        fn paths_cover_every_edge_once_test() {
+            let config = GenerationConfig {
+                cell_count: 500,
+                ..Default::default()
+            };
+            let graph = full_terrain_gen(&config, 11).unwrap().0;
+            let segment_count = |graph: &Graph, id: EdgeId| graph[id].corner_midpoints.len() - 1;
+
+            let rivers = river_paths(&graph);
+            let mut river_edges: Vec<EdgeId> =
+                rivers.iter().flat_map(|r| r.edges.clone()).collect();
+            river_edges.sort();
+            let expected: Vec<EdgeId> = graph
+                .edge_ids()
+                .filter(|id| graph[*id].river > 0.0)
+                .collect();
+            assert!(!expected.is_empty());
+            assert_eq!(river_edges, expected);
+            for river in &rivers {
+                let segments: usize = river
+                    .edges
+                    .iter()
+                    .map(|id| segment_count(&graph, *id))
+                    .sum();
+                assert_eq!(river.points.len(), segments + 1);
+                assert!(river.volume > 0.0);
+            }
+
+            let coast_segments: usize = graph
+                .edge_ids()
+                .filter(|id| graph.edge_is_coastal(*id))
+                .map(|id| segment_count(&graph, id))
+                .sum();
+            let coastlines = coastline_paths(&graph);
+            assert_eq!(
+                coastlines.iter().map(|line| line.len() - 1).sum::<usize>(),
+                coast_segments
+            );
+        }
+    }
+}
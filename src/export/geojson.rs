@@ -0,0 +1,319 @@
+pub mod geojson {
+    use std::{fs, path::Path};
+
+    use crate::{
+        error::error::MapGenError,
+        export::export::{coastline_paths, river_paths},
+        graph2::graph2::Graph,
+    };
+
+    // NaN and infinity have no JSON representation
+    fn number(value: f32, name: &str) -> Result<f32, MapGenError> {
+        if !value.is_finite() {
+            return Err(MapGenError::NonFiniteValue(format!(
+                "{} is {}",
+                name, value
+            )));
+        }
+        return Ok(value);
+    }
+
+    // spherical maps are written as longitude and latitude so GIS tools can
+    // place them on a globe, flat maps keep their own units
+    fn coordinate(graph: &Graph, pos: (f32, f32)) -> Result<String, MapGenError> {
+        let x = number(pos.0, "x coordinate")?;
+        let y = number(pos.1, "y coordinate")?;
+        if graph.sphere {
+            let (latitude, longitude) = graph.get_lat_long((x, y));
+            // rounding can leave a point on the seam a hair past it
+            return Ok(format!("[{},{}]", longitude.clamp(-180.0, 180.0), latitude));
+        }
+        return Ok(format!("[{},{}]", x, y));
+    }
+
+    fn line(graph: &Graph, points: &[(f32, f32)]) -> Result<String, MapGenError> {
+        let coordinates = points
+            .iter()
+            .map(|p| coordinate(graph, *p))
+            .collect::<Result<Vec<String>, MapGenError>>()?;
+        return Ok(format!("[{}]", coordinates.join(",")));
+    }
+
+    // where the segment from `p` to `q` meets the vertical line at `x`
+    fn crossing(p: (f32, f32), q: (f32, f32), x: f32) -> (f32, f32) {
+        let t = (x - p.0) / (q.0 - p.0);
+        return (x, p.1 + (q.1 - p.1) * t);
+    }
+
+    // Sutherland-Hodgman clip of an open ring to one side of the line at `x`
+    fn clip_ring(ring: &[(f32, f32)], x: f32, keep_right: bool) -> Vec<(f32, f32)> {
+        let inside = |p: (f32, f32)| if keep_right { p.0 >= x } else { p.0 <= x };
+        let mut output: Vec<(f32, f32)> = Vec::new();
+        for (i, p) in ring.iter().enumerate() {
+            let q = ring[(i + 1) % ring.len()];
+            match (inside(*p), inside(q)) {
+                (true, true) => output.push(q),
+                (true, false) => output.push(crossing(*p, q, x)),
+                (false, true) => {
+                    output.push(crossing(*p, q, x));
+                    output.push(q);
+                }
+                (false, false) => {}
+            }
+        }
+        return output;
+    }
+
+    // RFC 7946 section 3.1.9: shapes crossing the antimeridian are cut along
+    // it. Outlines on a sphere are unwrapped past the seam, so each map width
+    // they reach into is clipped out and shifted back onto the map
+    fn split_ring(graph: &Graph, ring: Vec<(f32, f32)>) -> Vec<Vec<(f32, f32)>> {
+        if !graph.sphere || ring.is_empty() {
+            return vec![ring];
+        }
+        let width = graph.x_scale as f32;
+        let min_x = ring.iter().map(|p| p.0).fold(f32::INFINITY, f32::min);
+        let max_x = ring.iter().map(|p| p.0).fold(f32::NEG_INFINITY, f32::max);
+        let first_strip = (min_x / width).floor() as i32;
+        let last_strip = ((max_x / width).ceil() as i32 - 1).max(first_strip);
+        if first_strip == 0 && last_strip == 0 {
+            return vec![ring];
+        }
+        let mut output: Vec<Vec<(f32, f32)>> = Vec::new();
+        for strip in first_strip..=last_strip {
+            let left = strip as f32 * width;
+            let piece = clip_ring(&clip_ring(&ring, left, true), left + width, false);
+            // a ring that only touches the edge of a strip leaves a sliver
+            if piece.len() < 3 || piece.iter().all(|p| p.0 == piece[0].0) {
+                continue;
+            }
+            output.push(piece.iter().map(|p| (p.0 - left, p.1)).collect());
+        }
+        return output;
+    }
+
+    // the line version of `split_ring`, ending the line on the antimeridian
+    // and picking it up again on the far side
+    fn split_line(graph: &Graph, points: &[(f32, f32)]) -> Vec<Vec<(f32, f32)>> {
+        if !graph.sphere {
+            return vec![points.to_vec()];
+        }
+        let width = graph.x_scale as f32;
+        let strip_of = |p: (f32, f32)| (p.0 / width).floor();
+        let mut output: Vec<Vec<(f32, f32)>> = Vec::new();
+        let mut current: Vec<(f32, f32)> = Vec::new();
+        let mut strip = points.first().map_or(0.0, |p| strip_of(*p));
+        for (i, p) in points.iter().enumerate() {
+            let next_strip = strip_of(*p);
+            if next_strip != strip {
+                let seam = strip.max(next_strip) * width;
+                let cut = crossing(points[i - 1], *p, seam);
+                current.push((cut.0 - strip * width, cut.1));
+                output.push(current);
+                current = vec![(cut.0 - next_strip * width, cut.1)];
+                strip = next_strip;
+            }
+            let shifted = (p.0 - strip * width, p.1);
+            if current.last() != Some(&shifted) {
+                current.push(shifted);
+            }
+        }
+        output.push(current);
+        output.retain(|line| line.len() > 1);
+        return output;
+    }
+
+    fn feature(geometry_type: &str, coordinates: String, properties: String) -> String {
+        return format!(
+            "{{\"type\":\"Feature\",\"geometry\":{{\"type\":\"{}\",\"coordinates\":{}}},\"properties\":{{{}}}}}",
+            geometry_type, coordinates, properties
+        );
+    }
+
+    /// A GeoJSON `FeatureCollection` of the map: a `Polygon` for every cell,
+    /// a `LineString` for every river run and one `MultiLineString` holding
+    /// the coastlines. Each feature has a `kind` property of `cell`, `river`
+    /// or `coastline` to filter on. On spherical maps, cells and rivers that
+    /// cross the antimeridian are split into a `MultiPolygon` or
+    /// `MultiLineString`, so every longitude stays within ±180.
+    ///
+    /// Fails with `MapGenError::NonFiniteValue` rather than write a NaN or
+    /// infinite coordinate or property.
+    pub fn to_geojson(graph: &Graph) -> Result<String, MapGenError> {
+        let mut features: Vec<String> = Vec::new();
+        for cell_id in graph.cell_ids() {
+            let mut polygons: Vec<String> = Vec::new();
+            for mut ring in split_ring(graph, graph.get_cell_outline_with_midpoints(cell_id)?) {
+                if let Some(first) = ring.first().copied() {
+                    // GeoJSON rings repeat their first position at the end
+                    ring.push(first);
+                }
+                polygons.push(format!("[{}]", line(graph, &ring)?));
+            }
+            let cell = &graph[cell_id];
+            let properties = format!(
+                "\"kind\":\"cell\",\"id\":{},\"biome\":\"{:?}\",\"elevation\":{},\"moisture\":{},\"water\":{},\"ocean\":{},\"coast\":{}",
+                cell_id.0,
+                cell.biome,
+                number(graph.get_cell_elevation(cell_id), "cell elevation")?,
+                number(cell.moisture, "cell moisture")?,
+                cell.water,
+                cell.ocean,
+                cell.coast
+            );
+            if polygons.len() == 1 {
+                features.push(feature("Polygon", polygons.remove(0), properties));
+            } else {
+                features.push(feature(
+                    "MultiPolygon",
+                    format!("[{}]", polygons.join(",")),
+                    properties,
+                ));
+            }
+        }
+        for river in river_paths(graph) {
+            let properties = format!(
+                "\"kind\":\"river\",\"volume\":{}",
+                number(river.volume, "river volume")?
+            );
+            let mut lines = split_line(graph, &river.points)
+                .iter()
+                .map(|points| line(graph, points))
+                .collect::<Result<Vec<String>, MapGenError>>()?;
+            if lines.len() == 1 {
+                features.push(feature("LineString", lines.remove(0), properties));
+            } else {
+                features.push(feature(
+                    "MultiLineString",
+                    format!("[{}]", lines.join(",")),
+                    properties,
+                ));
+            }
+        }
+        let mut coastlines: Vec<String> = Vec::new();
+        for points in coastline_paths(graph) {
+            for points in split_line(graph, &points) {
+                coastlines.push(line(graph, &points)?);
+            }
+        }
+        features.push(feature(
+            "MultiLineString",
+            format!("[{}]", coastlines.join(",")),
+            String::from("\"kind\":\"coastline\""),
+        ));
+        return Ok(format!(
+            "{{\"type\":\"FeatureCollection\",\"features\":[\n{}\n]}}\n",
+            features.join(",\n")
+        ));
+    }
+
+    pub fn save_geojson(graph: &Graph, path: impl AsRef<Path>) -> Result<(), MapGenError> {
+        return fs::write(path, to_geojson(graph)?).map_err(|e| MapGenError::Io(e.to_string()));
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::{config::config::GenerationConfig, terrain2::terrain2::full_terrain_gen};
+
+        #[test]
+        fn geojson_export_test() {
+            let config = GenerationConfig {
+                cell_count: 300,
+                ..Default::default()
+            };
+            let graph = full_terrain_gen(&config, 4).unwrap().0;
+            let json: serde_json::Value =
+                serde_json::from_str(&to_geojson(&graph).unwrap()).unwrap();
+            assert_eq!(json["type"], "FeatureCollection");
+            let features = json["features"].as_array().unwrap();
+            let of_kind = |kind: &'static str| {
+                features
+                    .iter()
+                    .filter(move |f| f["properties"]["kind"] == kind)
+            };
+
+            assert_eq!(of_kind("cell").count(), 300);
+            for cell in of_kind("cell") {
+                assert_eq!(cell["geometry"]["type"], "Polygon");
+                let ring = cell["geometry"]["coordinates"][0].as_array().unwrap();
+                assert!(ring.len() >= 4);
+                assert_eq!(ring.first(), ring.last());
+            }
+            assert!(of_kind("river").count() > 0);
+            assert!(
+                of_kind("river").all(|river| river["properties"]["volume"].as_f64().unwrap() > 0.0)
+            );
+            let coastline = of_kind("coastline").next().unwrap();
+            assert_eq!(coastline["geometry"]["type"], "MultiLineString");
+            assert!(!coastline["geometry"]["coordinates"]
+                .as_array()
+                .unwrap()
+                .is_empty());
+        }
+
+        #[test]
+        fn sphere_geojson_stays_within_antimeridian_test() {
+            let config = GenerationConfig {
+                cell_count: 400,
+                x_scale: 800.0,
+                y_scale: 400.0,
+                sphere: true,
+                ..Default::default()
+            };
+            let graph = full_terrain_gen(&config, 6).unwrap().0;
+            let json: serde_json::Value =
+                serde_json::from_str(&to_geojson(&graph).unwrap()).unwrap();
+            let features = json["features"].as_array().unwrap();
+            let mut split_cells = 0;
+            for cell in features
+                .iter()
+                .filter(|f| f["properties"]["kind"] == "cell")
+            {
+                let coordinates = &cell["geometry"]["coordinates"];
+                let polygons = if cell["geometry"]["type"] == "MultiPolygon" {
+                    split_cells += 1;
+                    coordinates.as_array().unwrap().clone()
+                } else {
+                    vec![coordinates.clone()]
+                };
+                for polygon in polygons {
+                    let ring = polygon[0].as_array().unwrap();
+                    assert!(ring.len() >= 4);
+                    assert_eq!(ring.first(), ring.last());
+                    for point in ring {
+                        let longitude = point[0].as_f64().unwrap();
+                        let latitude = point[1].as_f64().unwrap();
+                        assert!((-180.0..=180.0).contains(&longitude));
+                        assert!((-90.0..=90.0).contains(&latitude));
+                    }
+                }
+            }
+            // the seam runs through a column of cells and both poles
+            assert!(split_cells > 2);
+        }
+
+        #[test]
+        fn non_finite_values_rejected_test() {
+            let config = GenerationConfig {
+                cell_count: 300,
+                ..Default::default()
+            };
+            let graph = full_terrain_gen(&config, 4).unwrap().0;
+
+            let mut moisture = graph.clone();
+            moisture.cells[0].moisture = f32::NAN;
+            assert!(matches!(
+                to_geojson(&moisture),
+                Err(MapGenError::NonFiniteValue(_))
+            ));
+
+            let mut position = graph.clone();
+            position.edges[0].corner_midpoints[1].0 = f32::INFINITY;
+            assert!(matches!(
+                to_geojson(&position),
+                Err(MapGenError::NonFiniteValue(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,251 @@
+pub mod heightmap {
+    use std::{fs, path::Path};
+
+    use crate::{
+        error::error::MapGenError,
+        graph2::graph2::{CellId, Graph},
+    };
+
+    /// A 16 bit grayscale heightmap, rows running from the top left. Samples
+    /// scale linearly from 0 at `min_elevation` to 65535 at `max_elevation`.
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Heightmap {
+        pub width: u32,
+        pub height: u32,
+        pub samples: Vec<u16>,
+        pub min_elevation: f32,
+        pub max_elevation: f32,
+    }
+
+    impl Heightmap {
+        /// The sample value of elevation 0, where the coast sits
+        pub fn sea_level(&self) -> u16 {
+            return quantise(0.0, self.min_elevation, self.max_elevation);
+        }
+
+        pub fn to_png(&self) -> Result<Vec<u8>, MapGenError> {
+            let mut bytes: Vec<u8> = Vec::new();
+            let mut encoder = png::Encoder::new(&mut bytes, self.width, self.height);
+            encoder.set_color(png::ColorType::Grayscale);
+            encoder.set_depth(png::BitDepth::Sixteen);
+            // png stores 16 bit samples big endian
+            let data: Vec<u8> = self.samples.iter().flat_map(|s| s.to_be_bytes()).collect();
+            encoder
+                .write_header()
+                .and_then(|mut writer| writer.write_image_data(&data))
+                .map_err(|e| MapGenError::Io(e.to_string()))?;
+            return Ok(bytes);
+        }
+
+        /// Headerless little endian samples, the `.r16` / `.raw` layout
+        /// terrain importers expect
+        pub fn to_r16(&self) -> Vec<u8> {
+            return self.samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+        }
+
+        pub fn save_png(&self, path: impl AsRef<Path>) -> Result<(), MapGenError> {
+            return fs::write(path, self.to_png()?).map_err(|e| MapGenError::Io(e.to_string()));
+        }
+
+        pub fn save_r16(&self, path: impl AsRef<Path>) -> Result<(), MapGenError> {
+            return fs::write(path, self.to_r16()).map_err(|e| MapGenError::Io(e.to_string()));
+        }
+    }
+
+    fn quantise(elevation: f32, min: f32, max: f32) -> u16 {
+        if max <= min {
+            return 0;
+        }
+        let t = ((elevation - min) / (max - min)).clamp(0.0, 1.0);
+        return (t * u16::MAX as f32).round() as u16;
+    }
+
+    // a map position with the elevation to interpolate there
+    type Vertex = ((f32, f32), f32);
+
+    // fans each cell from its centre to its corners. A ring around the pole of
+    // a spherical map has no centre inside it, so it is filled with strips up
+    // to the pole instead, which takes the cell's elevation
+    fn cell_triangles(graph: &Graph, cell_id: CellId) -> Result<Vec<[Vertex; 3]>, MapGenError> {
+        let corners = graph.get_cell_corners_in_order(cell_id)?;
+        if corners.is_empty() {
+            return Ok(vec![]);
+        }
+        let positions = graph.unwrap_path(corners.iter().map(|c| c.pos).collect());
+        let mut ring: Vec<Vertex> = positions
+            .iter()
+            .zip(&corners)
+            .map(|(pos, corner)| (*pos, corner.elevation))
+            .collect();
+        let center_elevation = graph.get_cell_elevation(cell_id);
+
+        let (first, last) = (ring[0], ring[ring.len() - 1]);
+        let wrapped_first = graph.unwrap_pos(last.0, first.0);
+        let mut output: Vec<[Vertex; 3]> = Vec::new();
+        if wrapped_first != first.0 {
+            ring.push((wrapped_first, first.1));
+            let mean_y = ring.iter().map(|v| v.0 .1).sum::<f32>() / ring.len() as f32;
+            let pole_y = if mean_y > graph.y_scale as f32 / 2.0 {
+                graph.y_scale as f32
+            } else {
+                0.0
+            };
+            for pair in ring.windows(2) {
+                let (a, b) = (pair[0], pair[1]);
+                let a_pole = ((a.0 .0, pole_y), center_elevation);
+                let b_pole = ((b.0 .0, pole_y), center_elevation);
+                output.push([a, b, b_pole]);
+                output.push([a, b_pole, a_pole]);
+            }
+            return Ok(output);
+        }
+
+        let center = graph.unwrap_pos(first.0, graph.get_cell_center(cell_id));
+        for (i, a) in ring.iter().enumerate() {
+            let b = ring[(i + 1) % ring.len()];
+            output.push([(center, center_elevation), *a, b]);
+        }
+        return Ok(output);
+    }
+
+    // elevation at every pixel centre, NaN where no triangle covers it
+    fn interpolate_elevations(
+        graph: &Graph,
+        width: u32,
+        height: u32,
+    ) -> Result<Vec<f32>, MapGenError> {
+        let (width, height) = (width as usize, height as usize);
+        let x_ratio = width as f32 / graph.x_scale as f32;
+        let y_ratio = height as f32 / graph.y_scale as f32;
+        let y_scale = graph.y_scale as f32;
+        let mut output = vec![f32::NAN; width * height];
+
+        for cell_id in graph.cell_ids() {
+            let triangles = cell_triangles(graph, cell_id)?;
+            let positions: Vec<(f32, f32)> = triangles.iter().flatten().map(|v| v.0).collect();
+            for offset in graph.get_seam_offsets(&positions) {
+                for triangle in &triangles {
+                    // image rows run down the page, the map's y runs up
+                    let [a, b, c] = triangle.map(|((x, y), elevation)| {
+                        ((x + offset) * x_ratio, (y_scale - y) * y_ratio, elevation)
+                    });
+                    let area = (b.0 - a.0) * (c.1 - a.1) - (c.0 - a.0) * (b.1 - a.1);
+                    if area == 0.0 {
+                        continue;
+                    }
+                    let min_x = a.0.min(b.0).min(c.0).floor().max(0.0) as usize;
+                    let max_x = (a.0.max(b.0).max(c.0).ceil().max(0.0) as usize).min(width);
+                    let min_y = a.1.min(b.1).min(c.1).floor().max(0.0) as usize;
+                    let max_y = (a.1.max(b.1).max(c.1).ceil().max(0.0) as usize).min(height);
+                    for y in min_y..max_y {
+                        for x in min_x..max_x {
+                            let p = (x as f32 + 0.5, y as f32 + 0.5);
+                            let w_a =
+                                ((b.0 - p.0) * (c.1 - p.1) - (c.0 - p.0) * (b.1 - p.1)) / area;
+                            let w_b =
+                                ((c.0 - p.0) * (a.1 - p.1) - (a.0 - p.0) * (c.1 - p.1)) / area;
+                            let w_c = 1.0 - w_a - w_b;
+                            // a little slack so pixel centres on shared edges
+                            // aren't lost to rounding
+                            if w_a >= -1e-4 && w_b >= -1e-4 && w_c >= -1e-4 {
+                                output[y * width + x] = w_a * a.2 + w_b * b.2 + w_c * c.2;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        return Ok(output);
+    }
+
+    /// Renders corner elevations to a heightmap at any resolution, smoothly
+    /// interpolated across each cell by fanning it into triangles from its
+    /// centre. The full elevation range of the map, sea floor included, is
+    /// stretched over the 16 bit range, see `Heightmap::sea_level`.
+    pub fn render_heightmap(
+        graph: &Graph,
+        width: u32,
+        height: u32,
+    ) -> Result<Heightmap, MapGenError> {
+        if width == 0 || height == 0 {
+            return Err(MapGenError::InvalidConfig(String::from(
+                "image size must be at least 1x1",
+            )));
+        }
+        let elevations = interpolate_elevations(graph, width, height)?;
+        let min_elevation = graph
+            .corners
+            .iter()
+            .map(|c| c.elevation)
+            .fold(f32::MAX, f32::min);
+        let max_elevation = graph
+            .corners
+            .iter()
+            .map(|c| c.elevation)
+            .fold(f32::MIN, f32::max);
+        let samples = elevations
+            .iter()
+            .map(|elevation| {
+                // anything missed along the map border takes the lowest value
+                let elevation = if elevation.is_nan() {
+                    min_elevation
+                } else {
+                    *elevation
+                };
+                return quantise(elevation, min_elevation, max_elevation);
+            })
+            .collect();
+        return Ok(Heightmap {
+            width,
+            height,
+            samples,
+            min_elevation,
+            max_elevation,
+        });
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::collections::BTreeSet;
+
+        use super::*;
+        use crate::{config::config::GenerationConfig, terrain2::terrain2::full_terrain_gen};
+
+        #[test]
+        fn heightmap_covers_every_pixel_test() {
+            for (wrap_x, sphere) in [(false, false), (true, false), (false, true)] {
+                let config = GenerationConfig {
+                    cell_count: 500,
+                    x_scale: 800.0,
+                    y_scale: 400.0,
+                    wrap_x,
+                    sphere,
+                    ..Default::default()
+                };
+                let graph = full_terrain_gen(&config, 6).unwrap().0;
+                let elevations = interpolate_elevations(&graph, 300, 150).unwrap();
+                assert!(elevations.iter().all(|e| !e.is_nan()));
+            }
+        }
+
+        #[test]
+        fn heightmap_is_smooth_test() {
+            let config = GenerationConfig {
+                cell_count: 500,
+                ..Default::default()
+            };
+            let graph = full_terrain_gen(&config, 6).unwrap().0;
+            let heightmap = render_heightmap(&graph, 400, 200).unwrap();
+            assert_eq!(heightmap.samples.len(), 400 * 200);
+            // pixel centres rarely land exactly on the highest corner
+            assert!(*heightmap.samples.iter().max().unwrap() > u16::MAX / 10 * 9);
+            assert!(heightmap.sea_level() > 0);
+            // one flat value per cell would give at most one sample per cell
+            let distinct: BTreeSet<u16> = heightmap.samples.iter().copied().collect();
+            assert!(distinct.len() > graph.cells.len() * 10);
+
+            assert_eq!(heightmap.to_r16().len(), 400 * 200 * 2);
+            assert_eq!(&heightmap.to_png().unwrap()[1..4], b"PNG");
+        }
+    }
+}
@@ -0,0 +1,361 @@
+pub mod mesh {
+    use std::{f32::consts::TAU, fs, path::Path};
+
+    use crate::{
+        error::error::MapGenError,
+        graph2::graph2::Graph,
+        palette::palette::{cell_colour, to_srgb8},
+    };
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum MeshFormat {
+        /// Wavefront OBJ, with vertex colours after each position
+        Obj,
+        /// Binary little endian PLY
+        Ply,
+        /// Binary glTF 2.0 (.glb)
+        Gltf,
+    }
+
+    /// A triangle mesh of the map with z up. Each cell is fanned from its
+    /// centre to its corners and has its own copy of those vertices, so it
+    /// keeps a flat biome colour, while normals are shared between copies
+    /// so the surface shades smoothly.
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Mesh {
+        pub positions: Vec<[f32; 3]>,
+        pub normals: Vec<[f32; 3]>,
+        /// Linear rgb, from the `renderer2` palette
+        pub colours: Vec<(f32, f32, f32)>,
+        pub triangles: Vec<[u32; 3]>,
+    }
+
+    fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+        return [a[0] - b[0], a[1] - b[1], a[2] - b[2]];
+    }
+
+    fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+        return [
+            a[1] * b[2] - a[2] * b[1],
+            a[2] * b[0] - a[0] * b[2],
+            a[0] * b[1] - a[1] * b[0],
+        ];
+    }
+
+    fn normalise(v: [f32; 3]) -> [f32; 3] {
+        let length = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+        if length == 0.0 {
+            return [0.0, 0.0, 1.0];
+        }
+        return [v[0] / length, v[1] / length, v[2] / length];
+    }
+
+    /// Builds the mesh, raising each vertex by its elevation times
+    /// `vertical_scale`. Cell centres take `get_cell_elevation`. Spherical
+    /// maps become a globe with the same circumference as the map width.
+    pub fn build_mesh(graph: &Graph, vertical_scale: f32) -> Result<Mesh, MapGenError> {
+        let radius = graph.x_scale as f32 / TAU;
+        let place = |pos: (f32, f32), elevation: f32| -> [f32; 3] {
+            if graph.sphere {
+                let (x, y, z) = graph.get_position_3d(pos);
+                let r = radius + elevation * vertical_scale;
+                return [x * r, y * r, z * r];
+            }
+            return [pos.0, pos.1, elevation * vertical_scale];
+        };
+
+        let mut mesh = Mesh {
+            positions: Vec::new(),
+            normals: Vec::new(),
+            colours: Vec::new(),
+            triangles: Vec::new(),
+        };
+        // normals are summed per corner and per cell centre, which every copy
+        // of that vertex then shares
+        let mut shared_normals = vec![[0.0_f32; 3]; graph.corners.len() + graph.cells.len()];
+        let mut vertex_slots: Vec<usize> = Vec::new();
+
+        for cell_id in graph.cell_ids() {
+            let corner_ids = graph.get_cell_corner_ids_in_order(cell_id)?;
+            if corner_ids.is_empty() {
+                continue;
+            }
+            let outline = graph.unwrap_path(corner_ids.iter().map(|id| graph[*id].pos).collect());
+            let colour = cell_colour(&graph[cell_id]);
+            let center_elevation = graph.get_cell_elevation(cell_id);
+            let center = if graph.sphere {
+                let (x, y, z) = graph.get_cell_center_3d(cell_id);
+                let r = radius + center_elevation * vertical_scale;
+                [x * r, y * r, z * r]
+            } else {
+                let pos = graph.unwrap_pos(outline[0], graph.get_cell_center(cell_id));
+                place(pos, center_elevation)
+            };
+
+            let center_index = mesh.positions.len() as u32;
+            mesh.positions.push(center);
+            mesh.colours.push(colour);
+            vertex_slots.push(graph.corners.len() + cell_id.index());
+            for (corner_id, pos) in corner_ids.iter().zip(&outline) {
+                mesh.positions
+                    .push(place(*pos, graph[*corner_id].elevation));
+                mesh.colours.push(colour);
+                vertex_slots.push(corner_id.index());
+            }
+
+            let ring_len = corner_ids.len() as u32;
+            for i in 0..ring_len {
+                let mut triangle = [
+                    center_index,
+                    center_index + 1 + i,
+                    center_index + 1 + (i + 1) % ring_len,
+                ];
+                let [a, b, c] = triangle.map(|index| mesh.positions[index as usize]);
+                let mut normal = cross(sub(b, a), sub(c, a));
+                // wind every face to point up, or out from a globe
+                let up = if graph.sphere { a } else { [0.0, 0.0, 1.0] };
+                if normal[0] * up[0] + normal[1] * up[1] + normal[2] * up[2] < 0.0 {
+                    triangle.swap(1, 2);
+                    normal = normal.map(|n| -n);
+                }
+                for index in triangle {
+                    let slot = &mut shared_normals[vertex_slots[index as usize]];
+                    *slot = [
+                        slot[0] + normal[0],
+                        slot[1] + normal[1],
+                        slot[2] + normal[2],
+                    ];
+                }
+                mesh.triangles.push(triangle);
+            }
+        }
+        mesh.normals = vertex_slots
+            .iter()
+            .map(|slot| normalise(shared_normals[*slot]))
+            .collect();
+        return Ok(mesh);
+    }
+
+    impl Mesh {
+        pub fn to_obj(&self) -> String {
+            let mut output = String::from("# voronoi-map-gen\n");
+            for (position, colour) in self.positions.iter().zip(&self.colours) {
+                let [r, g, b] = to_srgb8(*colour).map(|c| c as f32 / 255.0);
+                output.push_str(&format!(
+                    "v {} {} {} {:.4} {:.4} {:.4}\n",
+                    position[0], position[1], position[2], r, g, b
+                ));
+            }
+            for normal in &self.normals {
+                output.push_str(&format!(
+                    "vn {:.5} {:.5} {:.5}\n",
+                    normal[0], normal[1], normal[2]
+                ));
+            }
+            for triangle in &self.triangles {
+                // obj indices count from 1
+                let [a, b, c] = triangle.map(|index| index + 1);
+                output.push_str(&format!("f {a}//{a} {b}//{b} {c}//{c}\n"));
+            }
+            return output;
+        }
+
+        pub fn to_ply(&self) -> Vec<u8> {
+            let header = format!(
+                "ply\nformat binary_little_endian 1.0\ncomment voronoi-map-gen\n\
+                 element vertex {}\nproperty float x\nproperty float y\nproperty float z\n\
+                 property float nx\nproperty float ny\nproperty float nz\n\
+                 property uchar red\nproperty uchar green\nproperty uchar blue\n\
+                 element face {}\nproperty list uchar uint vertex_indices\nend_header\n",
+                self.positions.len(),
+                self.triangles.len()
+            );
+            let mut output = header.into_bytes();
+            for i in 0..self.positions.len() {
+                for value in self.positions[i].iter().chain(&self.normals[i]) {
+                    output.extend_from_slice(&value.to_le_bytes());
+                }
+                output.extend_from_slice(&to_srgb8(self.colours[i]));
+            }
+            for triangle in &self.triangles {
+                output.push(3);
+                for index in triangle {
+                    output.extend_from_slice(&index.to_le_bytes());
+                }
+            }
+            return output;
+        }
+
+        /// Binary glTF. glTF is y up, so the map's z becomes y and its y
+        /// runs along -z.
+        pub fn to_glb(&self) -> Vec<u8> {
+            let y_up = |v: &[f32; 3]| [v[0], v[2], -v[1]];
+            let positions: Vec<[f32; 3]> = self.positions.iter().map(y_up).collect();
+            let normals: Vec<[f32; 3]> = self.normals.iter().map(y_up).collect();
+
+            let mut buffer: Vec<u8> = Vec::new();
+            for v in positions.iter().chain(&normals) {
+                v.iter()
+                    .for_each(|f| buffer.extend_from_slice(&f.to_le_bytes()));
+            }
+            for (r, g, b) in &self.colours {
+                [r, g, b]
+                    .iter()
+                    .for_each(|f| buffer.extend_from_slice(&f.to_le_bytes()));
+            }
+            for triangle in &self.triangles {
+                triangle
+                    .iter()
+                    .for_each(|i| buffer.extend_from_slice(&i.to_le_bytes()));
+            }
+
+            let vertex_bytes = positions.len() * 12;
+            let index_bytes = self.triangles.len() * 12;
+            let min = positions.iter().fold([f32::MAX; 3], |acc, v| {
+                return [acc[0].min(v[0]), acc[1].min(v[1]), acc[2].min(v[2])];
+            });
+            let max = positions.iter().fold([f32::MIN; 3], |acc, v| {
+                return [acc[0].max(v[0]), acc[1].max(v[1]), acc[2].max(v[2])];
+            });
+            let view = |index: usize, length: usize, target: u32| {
+                format!(
+                    "{{\"buffer\":0,\"byteOffset\":{},\"byteLength\":{},\"target\":{}}}",
+                    index * vertex_bytes,
+                    length,
+                    target
+                )
+            };
+            let json = format!(
+                concat!(
+                    "{{\"asset\":{{\"version\":\"2.0\",\"generator\":\"voronoi-map-gen\"}},",
+                    "\"scene\":0,\"scenes\":[{{\"nodes\":[0]}}],\"nodes\":[{{\"mesh\":0}}],",
+                    "\"meshes\":[{{\"primitives\":[{{\"attributes\":{{\"POSITION\":0,\"NORMAL\":1,\"COLOR_0\":2}},\"indices\":3,\"mode\":4}}]}}],",
+                    "\"buffers\":[{{\"byteLength\":{}}}],",
+                    "\"bufferViews\":[{},{},{},{}],",
+                    "\"accessors\":[",
+                    "{{\"bufferView\":0,\"componentType\":5126,\"count\":{},\"type\":\"VEC3\",\"min\":[{},{},{}],\"max\":[{},{},{}]}},",
+                    "{{\"bufferView\":1,\"componentType\":5126,\"count\":{},\"type\":\"VEC3\"}},",
+                    "{{\"bufferView\":2,\"componentType\":5126,\"count\":{},\"type\":\"VEC3\"}},",
+                    "{{\"bufferView\":3,\"componentType\":5125,\"count\":{},\"type\":\"SCALAR\"}}]}}"
+                ),
+                buffer.len(),
+                view(0, vertex_bytes, 34962),
+                view(1, vertex_bytes, 34962),
+                view(2, vertex_bytes, 34962),
+                view(3, index_bytes, 34963),
+                positions.len(),
+                min[0],
+                min[1],
+                min[2],
+                max[0],
+                max[1],
+                max[2],
+                positions.len(),
+                positions.len(),
+                self.triangles.len() * 3
+            );
+
+            // both chunks are padded to 4 bytes, json with spaces
+            let mut json = json.into_bytes();
+            while !json.len().is_multiple_of(4) {
+                json.push(b' ');
+            }
+            while !buffer.len().is_multiple_of(4) {
+                buffer.push(0);
+            }
+            let total_length = 12 + 8 + json.len() + 8 + buffer.len();
+            let mut output: Vec<u8> = Vec::with_capacity(total_length);
+            output.extend_from_slice(b"glTF");
+            output.extend_from_slice(&2_u32.to_le_bytes());
+            output.extend_from_slice(&(total_length as u32).to_le_bytes());
+            output.extend_from_slice(&(json.len() as u32).to_le_bytes());
+            output.extend_from_slice(b"JSON");
+            output.extend_from_slice(&json);
+            output.extend_from_slice(&(buffer.len() as u32).to_le_bytes());
+            output.extend_from_slice(b"BIN\0");
+            output.extend_from_slice(&buffer);
+            return output;
+        }
+
+        pub fn to_bytes(&self, format: MeshFormat) -> Vec<u8> {
+            return match format {
+                MeshFormat::Obj => self.to_obj().into_bytes(),
+                MeshFormat::Ply => self.to_ply(),
+                MeshFormat::Gltf => self.to_glb(),
+            };
+        }
+    }
+
+    pub fn save_mesh(
+        graph: &Graph,
+        path: impl AsRef<Path>,
+        format: MeshFormat,
+        vertical_scale: f32,
+    ) -> Result<(), MapGenError> {
+        let mesh = build_mesh(graph, vertical_scale)?;
+        return fs::write(path, mesh.to_bytes(format)).map_err(|e| MapGenError::Io(e.to_string()));
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::{config::config::GenerationConfig, terrain2::terrain2::full_terrain_gen};
+
+        fn test_graph(sphere: bool) -> Graph {
+            let config = GenerationConfig {
+                cell_count: 300,
+                sphere,
+                ..Default::default()
+            };
+            return full_terrain_gen(&config, 2).unwrap().0;
+        }
+
+        #[test]
+        fn mesh_faces_point_outwards_test() {
+            for sphere in [false, true] {
+                let graph = test_graph(sphere);
+                let mesh = build_mesh(&graph, 50.0).unwrap();
+                let corner_count: usize = graph.cells.iter().map(|c| c.edges.len()).sum();
+                assert_eq!(mesh.triangles.len(), corner_count);
+                assert_eq!(mesh.positions.len(), corner_count + graph.cells.len());
+                for (position, normal) in mesh.positions.iter().zip(&mesh.normals) {
+                    let up = if sphere { *position } else { [0.0, 0.0, 1.0] };
+                    assert!(normal[0] * up[0] + normal[1] * up[1] + normal[2] * up[2] > 0.0);
+                }
+            }
+        }
+
+        #[test]
+        fn mesh_formats_test() {
+            let mesh = build_mesh(&test_graph(false), 50.0).unwrap();
+            let (vertices, faces) = (mesh.positions.len(), mesh.triangles.len());
+
+            let obj = mesh.to_obj();
+            assert_eq!(
+                obj.lines().filter(|l| l.starts_with("v ")).count(),
+                vertices
+            );
+            assert_eq!(
+                obj.lines().filter(|l| l.starts_with("vn ")).count(),
+                vertices
+            );
+            assert_eq!(obj.lines().filter(|l| l.starts_with("f ")).count(), faces);
+
+            let ply = mesh.to_ply();
+            let header_end = ply.windows(11).position(|w| w == b"end_header\n").unwrap() + 11;
+            assert_eq!(ply.len() - header_end, vertices * 27 + faces * 13);
+
+            let glb = mesh.to_glb();
+            assert_eq!(&glb[0..4], b"glTF");
+            assert_eq!(
+                u32::from_le_bytes(glb[8..12].try_into().unwrap()) as usize,
+                glb.len()
+            );
+            let json_length = u32::from_le_bytes(glb[12..16].try_into().unwrap()) as usize;
+            let json: serde_json::Value =
+                serde_json::from_slice(&glb[20..20 + json_length]).unwrap();
+            assert_eq!(json["accessors"][0]["count"], vertices);
+            assert_eq!(json["accessors"][3]["count"], faces * 3);
+        }
+    }
+}
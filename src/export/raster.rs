@@ -0,0 +1,381 @@
+pub mod raster {
+    use std::{f32::consts::TAU, fs, path::Path};
+
+    use crate::{
+        error::error::MapGenError,
+        export::export::coastline_paths,
+        graph2::graph2::Graph,
+        palette::palette::{cell_colour, to_srgb8, FRESH_WATER},
+    };
+
+    const WHITE: (f32, f32, f32) = (1.0, 1.0, 1.0);
+    const BLACK: (f32, f32, f32) = (0.0, 0.0, 0.0);
+    // segments used to approximate each round line cap
+    const CAP_SEGMENTS: usize = 12;
+
+    /// An 8 bit RGBA image, rows running from the top left
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct RasterImage {
+        pub width: u32,
+        pub height: u32,
+        pub pixels: Vec<u8>,
+    }
+
+    impl RasterImage {
+        pub fn to_png(&self) -> Result<Vec<u8>, MapGenError> {
+            let mut bytes: Vec<u8> = Vec::new();
+            let mut encoder = png::Encoder::new(&mut bytes, self.width, self.height);
+            encoder.set_color(png::ColorType::Rgba);
+            encoder.set_depth(png::BitDepth::Eight);
+            encoder
+                .write_header()
+                .and_then(|mut writer| writer.write_image_data(&self.pixels))
+                .map_err(|e| MapGenError::Io(e.to_string()))?;
+            return Ok(bytes);
+        }
+
+        pub fn save_png(&self, path: impl AsRef<Path>) -> Result<(), MapGenError> {
+            return fs::write(path, self.to_png()?).map_err(|e| MapGenError::Io(e.to_string()));
+        }
+    }
+
+    /// Exact area coverage of a set of polygons over a small window of
+    /// pixels. Edges add their signed area to the cells they cross, and a
+    /// running sum along each row turns that into coverage, which gives
+    /// anti-aliased edges without supersampling. Overlapping polygons with the
+    /// same winding are clamped to full coverage.
+    struct Coverage {
+        left: i32,
+        top: i32,
+        width: usize,
+        height: usize,
+        accumulation: Vec<f32>,
+    }
+
+    impl Coverage {
+        fn new(bounds: ((f32, f32), (f32, f32))) -> Coverage {
+            let ((min_x, min_y), (max_x, max_y)) = bounds;
+            let left = min_x.floor() as i32 - 1;
+            let top = min_y.floor() as i32 - 1;
+            // one spare column on the right for edges ending on the last pixel
+            let width = (max_x.ceil() as i32 - left + 3).max(1) as usize;
+            let height = (max_y.ceil() as i32 - top + 2).max(1) as usize;
+            return Coverage {
+                left,
+                top,
+                width,
+                height,
+                accumulation: vec![0.0; width * height],
+            };
+        }
+
+        fn add_polygon(&mut self, points: &[(f32, f32)]) {
+            let area: f32 = points
+                .iter()
+                .enumerate()
+                .map(|(i, a)| {
+                    let b = points[(i + 1) % points.len()];
+                    return a.0 * b.1 - b.0 * a.1;
+                })
+                .sum();
+            // wind every polygon the same way, so overlaps add rather than cancel
+            let sign = if area < 0.0 { -1.0 } else { 1.0 };
+            for (i, a) in points.iter().enumerate() {
+                let b = points[(i + 1) % points.len()];
+                let local = |p: (f32, f32)| (p.0 - self.left as f32, p.1 - self.top as f32);
+                self.add_line(local(*a), local(b), sign);
+            }
+        }
+
+        fn add_line(&mut self, p0: (f32, f32), p1: (f32, f32), sign: f32) {
+            if p0.1 == p1.1 {
+                return;
+            }
+            let (direction, p0, p1) = if p0.1 < p1.1 {
+                (sign, p0, p1)
+            } else {
+                (-sign, p1, p0)
+            };
+            let dxdy = (p1.0 - p0.0) / (p1.1 - p0.1);
+            let mut x = p0.0;
+            for y in (p0.1 as usize)..(p1.1.ceil() as usize).min(self.height) {
+                let row = y * self.width;
+                let dy = ((y + 1) as f32).min(p1.1) - (y as f32).max(p0.1);
+                let x_next = x + dxdy * dy;
+                let d = dy * direction;
+                let (x0, x1) = if x < x_next { (x, x_next) } else { (x_next, x) };
+                let x0_floor = x0.floor();
+                let x0_index = x0_floor as usize;
+                let x1_ceil = x1.ceil();
+                let x1_index = x1_ceil as usize;
+                if x1_index <= x0_index + 1 {
+                    // the edge stays within one pixel on this row
+                    let x_mid = 0.5 * (x + x_next) - x0_floor;
+                    self.accumulation[row + x0_index] += d - d * x_mid;
+                    self.accumulation[row + x0_index + 1] += d * x_mid;
+                } else {
+                    let s = (x1 - x0).recip();
+                    let x0_fraction = x0 - x0_floor;
+                    let a0 = 0.5 * s * (1.0 - x0_fraction).powi(2);
+                    let x1_fraction = x1 - x1_ceil + 1.0;
+                    let a_end = 0.5 * s * x1_fraction.powi(2);
+                    self.accumulation[row + x0_index] += d * a0;
+                    if x1_index == x0_index + 2 {
+                        self.accumulation[row + x0_index + 1] += d * (1.0 - a0 - a_end);
+                    } else {
+                        let a1 = s * (1.5 - x0_fraction);
+                        self.accumulation[row + x0_index + 1] += d * (a1 - a0);
+                        for x_index in (x0_index + 2)..(x1_index - 1) {
+                            self.accumulation[row + x_index] += d * s;
+                        }
+                        let a2 = a1 + (x1_index - x0_index - 3) as f32 * s;
+                        self.accumulation[row + x1_index - 1] += d * (1.0 - a2 - a_end);
+                    }
+                    self.accumulation[row + x1_index] += d * a_end;
+                }
+                x = x_next;
+            }
+        }
+
+        // calls `f` with the image position and coverage of every touched pixel
+        fn for_each_pixel(&self, mut f: impl FnMut(i32, i32, f32)) {
+            for y in 0..self.height {
+                let mut sum = 0.0;
+                for x in 0..self.width {
+                    sum += self.accumulation[y * self.width + x];
+                    let coverage = sum.abs().min(1.0);
+                    if coverage > 0.0 {
+                        f(self.left + x as i32, self.top + y as i32, coverage);
+                    }
+                }
+            }
+        }
+    }
+
+    fn bounds(points: &[(f32, f32)]) -> ((f32, f32), (f32, f32)) {
+        return points.iter().fold(
+            ((f32::MAX, f32::MAX), (f32::MIN, f32::MIN)),
+            |(min, max), p| {
+                (
+                    (min.0.min(p.0), min.1.min(p.1)),
+                    (max.0.max(p.0), max.1.max(p.1)),
+                )
+            },
+        );
+    }
+
+    // a polyline of the given width with round caps and joins, as one
+    // polygon per segment and one per joint
+    fn line_coverage(points: &[(f32, f32)], width: f32) -> Coverage {
+        let radius = width / 2.0;
+        let padded = bounds(points);
+        let mut coverage = Coverage::new((
+            (padded.0 .0 - radius, padded.0 .1 - radius),
+            (padded.1 .0 + radius, padded.1 .1 + radius),
+        ));
+        for pair in points.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            let length = ((b.0 - a.0).powi(2) + (b.1 - a.1).powi(2)).sqrt();
+            if length == 0.0 {
+                continue;
+            }
+            let normal = (
+                -(b.1 - a.1) / length * radius,
+                (b.0 - a.0) / length * radius,
+            );
+            coverage.add_polygon(&[
+                (a.0 + normal.0, a.1 + normal.1),
+                (b.0 + normal.0, b.1 + normal.1),
+                (b.0 - normal.0, b.1 - normal.1),
+                (a.0 - normal.0, a.1 - normal.1),
+            ]);
+        }
+        for p in points {
+            let cap: Vec<(f32, f32)> = (0..CAP_SEGMENTS)
+                .map(|i| {
+                    let angle = i as f32 / CAP_SEGMENTS as f32 * TAU;
+                    return (p.0 + radius * angle.cos(), p.1 + radius * angle.sin());
+                })
+                .collect();
+            coverage.add_polygon(&cap);
+        }
+        return coverage;
+    }
+
+    struct Canvas {
+        width: usize,
+        height: usize,
+        // linear rgb, blended the same way the viewer's float frame is
+        colours: Vec<(f32, f32, f32)>,
+    }
+
+    impl Canvas {
+        fn blend(&mut self, coverage: &Coverage, colour: (f32, f32, f32)) {
+            let (width, height) = (self.width as i32, self.height as i32);
+            coverage.for_each_pixel(|x, y, alpha| {
+                if x < 0 || y < 0 || x >= width || y >= height {
+                    return;
+                }
+                let pixel = &mut self.colours[y as usize * self.width + x as usize];
+                pixel.0 += (colour.0 - pixel.0) * alpha;
+                pixel.1 += (colour.1 - pixel.1) * alpha;
+                pixel.2 += (colour.2 - pixel.2) * alpha;
+            });
+        }
+    }
+
+    /// Rasterises the map on the CPU at any resolution, with the `renderer2`
+    /// palette and line weights scaled to the image size. Biome labels are
+    /// left out, as there is no font to draw them with.
+    pub fn render_rgba(graph: &Graph, width: u32, height: u32) -> Result<RasterImage, MapGenError> {
+        if width == 0 || height == 0 {
+            return Err(MapGenError::InvalidConfig(String::from(
+                "image size must be at least 1x1",
+            )));
+        }
+        let x_ratio = width as f32 / graph.x_scale as f32;
+        let y_ratio = height as f32 / graph.y_scale as f32;
+        let line_scale = (x_ratio * y_ratio).sqrt();
+        let y_scale = graph.y_scale as f32;
+        // image rows run down the page, the map's y runs up the screen
+        let to_pixels = |points: &[(f32, f32)], offset: f32| -> Vec<(f32, f32)> {
+            return points
+                .iter()
+                .map(|p| ((p.0 + offset) * x_ratio, (y_scale - p.1) * y_ratio))
+                .collect();
+        };
+
+        let mut canvas = Canvas {
+            width: width as usize,
+            height: height as usize,
+            colours: vec![WHITE; width as usize * height as usize],
+        };
+
+        // cells tile the map, so their coverage is summed and the colours
+        // averaged by it, leaving no background showing along shared edges
+        let mut cell_colours = vec![(0.0, 0.0, 0.0); canvas.colours.len()];
+        let mut cell_coverage = vec![0.0_f32; canvas.colours.len()];
+        for cell_id in graph.cell_ids() {
+            let colour = cell_colour(&graph[cell_id]);
+            let outline = graph.get_cell_outline_with_midpoints(cell_id)?;
+            for offset in graph.get_seam_offsets(&outline) {
+                let points = to_pixels(&outline, offset);
+                let mut coverage = Coverage::new(bounds(&points));
+                coverage.add_polygon(&points);
+                coverage.for_each_pixel(|x, y, alpha| {
+                    if x < 0 || y < 0 || x >= width as i32 || y >= height as i32 {
+                        return;
+                    }
+                    let index = y as usize * width as usize + x as usize;
+                    let sum = &mut cell_colours[index];
+                    sum.0 += colour.0 * alpha;
+                    sum.1 += colour.1 * alpha;
+                    sum.2 += colour.2 * alpha;
+                    cell_coverage[index] += alpha;
+                });
+            }
+        }
+        for (index, pixel) in canvas.colours.iter_mut().enumerate() {
+            let total = cell_coverage[index];
+            if total > 0.0 {
+                let sum = cell_colours[index];
+                let average = (sum.0 / total, sum.1 / total, sum.2 / total);
+                let alpha = total.min(1.0);
+                *pixel = (
+                    pixel.0 + (average.0 - pixel.0) * alpha,
+                    pixel.1 + (average.1 - pixel.1) * alpha,
+                    pixel.2 + (average.2 - pixel.2) * alpha,
+                );
+            }
+        }
+
+        for edge_id in graph.edge_ids() {
+            let edge = &graph[edge_id];
+            if edge.river <= 0.0 {
+                continue;
+            }
+            let path = graph.unwrap_path(graph.get_edge_path_from(edge_id, edge.corners.0));
+            for offset in graph.get_seam_offsets(&path) {
+                let weight = edge.river.sqrt() * 0.3 * line_scale;
+                canvas.blend(
+                    &line_coverage(&to_pixels(&path, offset), weight),
+                    FRESH_WATER,
+                );
+            }
+        }
+        for path in coastline_paths(graph) {
+            for offset in graph.get_seam_offsets(&path) {
+                canvas.blend(
+                    &line_coverage(&to_pixels(&path, offset), 3.0 * line_scale),
+                    BLACK,
+                );
+            }
+        }
+
+        let mut pixels: Vec<u8> = Vec::with_capacity(canvas.colours.len() * 4);
+        for colour in &canvas.colours {
+            pixels.extend_from_slice(&to_srgb8(*colour));
+            pixels.push(255);
+        }
+        return Ok(RasterImage {
+            width,
+            height,
+            pixels,
+        });
+    }
+
+    pub fn save_png(
+        graph: &Graph,
+        path: impl AsRef<Path>,
+        width: u32,
+        height: u32,
+    ) -> Result<(), MapGenError> {
+        return render_rgba(graph, width, height)?.save_png(path);
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::{
+            config::config::GenerationConfig, palette::palette::SALT_WATER,
+            terrain2::terrain2::full_terrain_gen,
+        };
+
+        #[test]
+        fn coverage_is_exact_for_a_square_test() {
+            let mut coverage = Coverage::new(((0.0, 0.0), (4.0, 4.0)));
+            coverage.add_polygon(&[(1.0, 1.0), (3.0, 1.0), (3.0, 3.0), (1.0, 3.0)]);
+            let mut total = 0.0;
+            coverage.for_each_pixel(|_x, _y, alpha| total += alpha);
+            assert!((total - 4.0).abs() < 1e-4);
+
+            // half pixel offsets split the edge pixels' coverage
+            let mut coverage = Coverage::new(((0.0, 0.0), (4.0, 4.0)));
+            coverage.add_polygon(&[(0.5, 0.5), (2.5, 0.5), (2.5, 2.5), (0.5, 2.5)]);
+            let mut corner = 0.0;
+            coverage.for_each_pixel(|x, y, alpha| {
+                if (x, y) == (0, 0) {
+                    corner = alpha;
+                }
+            });
+            assert!((corner - 0.25).abs() < 1e-4);
+        }
+
+        #[test]
+        fn render_rgba_test() {
+            let config = GenerationConfig {
+                cell_count: 300,
+                ..Default::default()
+            };
+            let graph = full_terrain_gen(&config, 4).unwrap().0;
+            let image = render_rgba(&graph, 400, 200).unwrap();
+            assert_eq!(image.pixels.len(), 400 * 200 * 4);
+            // the border cells are always ocean
+            assert_eq!(&image.pixels[0..3], &to_srgb8(SALT_WATER));
+
+            let png = image.to_png().unwrap();
+            assert_eq!(&png[1..4], b"PNG");
+            assert!(render_rgba(&graph, 0, 10).is_err());
+        }
+    }
+}
@@ -0,0 +1,229 @@
+pub mod svg {
+    use std::{fs, path::Path};
+
+    use crate::{
+        error::error::MapGenError,
+        export::export::drawn_coastline_paths,
+        graph2::graph2::Graph,
+        palette::palette::{cell_colour, cell_short, to_srgb8, FRESH_WATER},
+    };
+
+    fn hex(colour: (f32, f32, f32)) -> String {
+        let [r, g, b] = to_srgb8(colour);
+        return format!("#{:02x}{:02x}{:02x}", r, g, b);
+    }
+
+    // svg y runs down the page, the map's y runs up the screen in renderer2
+    fn points_attribute(graph: &Graph, points: &[(f32, f32)], offset: f32) -> String {
+        let y_scale = graph.y_scale as f32;
+        let pairs: Vec<String> = points
+            .iter()
+            .map(|p| format!("{:.2},{:.2}", p.0 + offset, y_scale - p.1))
+            .collect();
+        return pairs.join(" ");
+    }
+
+    /// Draws the map as an SVG with the same colours and line weights as
+    /// `renderer2::render`, in one group per layer: `cells`, `rivers`,
+    /// `coast` and, with `biome_labels`, `labels`. A coastal edge carrying a
+    /// river is only drawn as a river. The view box is the map
+    /// bounds, so shapes crossing the seam of a wrapping map are drawn on
+    /// both sides and clipped.
+    pub fn to_svg(graph: &Graph, biome_labels: bool) -> Result<String, MapGenError> {
+        let mut cells: Vec<String> = Vec::new();
+        let mut labels: Vec<String> = Vec::new();
+        for cell_id in graph.cell_ids() {
+            let cell = &graph[cell_id];
+            let points = graph.get_cell_outline_with_midpoints(cell_id)?;
+            let colour = hex(cell_colour(cell));
+            for offset in graph.get_seam_offsets(&points) {
+                // a hairline stroke in the fill colour hides the anti-aliasing
+                // seams between neighbouring cells
+                cells.push(format!(
+                    "<polygon points=\"{}\" fill=\"{}\" stroke=\"{}\" stroke-width=\"0.5\"/>",
+                    points_attribute(graph, &points, offset),
+                    colour,
+                    colour
+                ));
+            }
+
+            let label = cell_short(cell);
+            if biome_labels && !label.is_empty() && !points.is_empty() {
+                let center = points.iter().fold((0.0, 0.0), |acc, (x, y)| {
+                    (
+                        acc.0 + x / points.len() as f32,
+                        acc.1 + y / points.len() as f32,
+                    )
+                });
+                let center = graph.wrap_pos(center);
+                labels.push(format!(
+                    "<text x=\"{:.2}\" y=\"{:.2}\">{}</text>",
+                    center.0,
+                    graph.y_scale as f32 - center.1,
+                    label
+                ));
+            }
+        }
+
+        let mut rivers: Vec<String> = Vec::new();
+        for edge_id in graph.edge_ids() {
+            let edge = &graph[edge_id];
+            if edge.river <= 0.0 {
+                continue;
+            }
+            let points = graph.unwrap_path(graph.get_edge_path_from(edge_id, edge.corners.0));
+            for offset in graph.get_seam_offsets(&points) {
+                rivers.push(format!(
+                    "<polyline points=\"{}\" stroke-width=\"{:.3}\"/>",
+                    points_attribute(graph, &points, offset),
+                    edge.river.sqrt() * 0.3
+                ));
+            }
+        }
+
+        let mut coast: Vec<String> = Vec::new();
+        for points in drawn_coastline_paths(graph) {
+            for offset in graph.get_seam_offsets(&points) {
+                coast.push(format!(
+                    "<polyline points=\"{}\"/>",
+                    points_attribute(graph, &points, offset)
+                ));
+            }
+        }
+
+        let mut output = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n",
+            w = graph.x_scale,
+            h = graph.y_scale
+        );
+        output.push_str("<rect width=\"100%\" height=\"100%\" fill=\"#ffffff\"/>\n");
+        let layers = [
+            ("cells", String::new(), cells),
+            (
+                "rivers",
+                format!(
+                    " fill=\"none\" stroke=\"{}\" stroke-linecap=\"round\" stroke-linejoin=\"round\"",
+                    hex(FRESH_WATER)
+                ),
+                rivers,
+            ),
+            (
+                "coast",
+                String::from(
+                    " fill=\"none\" stroke=\"#000000\" stroke-width=\"3\" stroke-linecap=\"round\" stroke-linejoin=\"round\"",
+                ),
+                coast,
+            ),
+            (
+                "labels",
+                String::from(
+                    " font-family=\"sans-serif\" font-size=\"7\" fill=\"#000000\" text-anchor=\"middle\" dominant-baseline=\"middle\"",
+                ),
+                labels,
+            ),
+        ];
+        for (id, attributes, elements) in layers {
+            if id == "labels" && !biome_labels {
+                continue;
+            }
+            output.push_str(&format!("<g id=\"{}\"{}>\n", id, attributes));
+            for element in elements {
+                output.push_str(&element);
+                output.push('\n');
+            }
+            output.push_str("</g>\n");
+        }
+        output.push_str("</svg>\n");
+        return Ok(output);
+    }
+
+    pub fn save_svg(
+        graph: &Graph,
+        path: impl AsRef<Path>,
+        biome_labels: bool,
+    ) -> Result<(), MapGenError> {
+        return fs::write(path, to_svg(graph, biome_labels)?)
+            .map_err(|e| MapGenError::Io(e.to_string()));
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::{config::config::GenerationConfig, terrain2::terrain2::full_terrain_gen};
+
+        #[test]
+        fn svg_export_test() {
+            let config = GenerationConfig {
+                cell_count: 300,
+                ..Default::default()
+            };
+            let graph = full_terrain_gen(&config, 4).unwrap().0;
+            let svg = to_svg(&graph, false).unwrap();
+            assert!(svg.starts_with("<svg"));
+            assert!(svg.trim_end().ends_with("</svg>"));
+            for layer in ["cells", "rivers", "coast"] {
+                assert!(svg.contains(&format!("<g id=\"{}\"", layer)));
+            }
+            assert!(!svg.contains("<g id=\"labels\""));
+            assert_eq!(svg.matches("<polygon").count(), 300);
+            assert_eq!(svg.matches("<g ").count(), svg.matches("</g>").count());
+
+            let labelled = to_svg(&graph, true).unwrap();
+            assert!(labelled.contains("<g id=\"labels\""));
+            let land_cells = graph
+                .cells
+                .iter()
+                .filter(|c| !cell_short(c).is_empty())
+                .count();
+            assert_eq!(labelled.matches("<text").count(), land_cells);
+        }
+
+        #[test]
+        fn shared_edges_drawn_once_test() {
+            let config = GenerationConfig {
+                cell_count: 300,
+                ..Default::default()
+            };
+            let mut graph = full_terrain_gen(&config, 4).unwrap().0;
+            let shared = graph
+                .edge_ids()
+                .find(|id| graph.edge_is_coastal(*id))
+                .unwrap();
+            graph.edges[shared.index()].river = 1.0;
+
+            // every line segment in a group, as its two end points
+            let svg = to_svg(&graph, false).unwrap();
+            let segments_in = |id: &str| {
+                let start = svg.find(&format!("<g id=\"{}\"", id)).unwrap();
+                let group = &svg[start..start + svg[start..].find("</g>").unwrap()];
+                return group
+                    .split("points=\"")
+                    .skip(1)
+                    .flat_map(|element| {
+                        let points: Vec<&str> =
+                            element[..element.find('"').unwrap()].split(' ').collect();
+                        return points
+                            .windows(2)
+                            .map(|pair| (pair[0].to_string(), pair[1].to_string()))
+                            .collect::<Vec<(String, String)>>();
+                    })
+                    .collect::<Vec<(String, String)>>();
+            };
+            let rivers = segments_in("rivers");
+            let coast = segments_in("coast");
+            let drawn_edges = graph
+                .edge_ids()
+                .filter(|id| graph[*id].river > 0.0 || graph.edge_is_coastal(*id));
+            let expected: usize = drawn_edges
+                .map(|id| graph[id].corner_midpoints.len() - 1)
+                .sum();
+            assert_eq!(rivers.len() + coast.len(), expected);
+            let shared_segment =
+                points_attribute(&graph, &graph[shared].corner_midpoints[..2], 0.0);
+            let (a, b) = shared_segment.split_once(' ').unwrap();
+            let matches = |(p, q): &(String, String)| (p == a && q == b) || (p == b && q == a);
+            assert_eq!(rivers.iter().filter(|s| matches(s)).count(), 1);
+            assert_eq!(coast.iter().filter(|s| matches(s)).count(), 0);
+        }
+    }
+}
@@ -2,6 +2,8 @@ pub mod graph {
     use std::collections::HashMap;
     use uuid::Uuid;
 
+    use crate::config::config::GenerationConfig;
+    use crate::error::error::MapGenError;
     use crate::voronoi::voronoi::initialise_voronoi;
 
     #[derive(Debug, Clone)]
@@ -88,7 +90,7 @@ pub mod graph {
             return edges
                 .iter()
                 .fold(Vec::new(), |acc: Vec<&Corner>, edge: &&Edge| {
-                    vec![acc, self.get_edge_corners(&edge.corners)].concat()
+                    [acc, self.get_edge_corners(&edge.corners)].concat()
                 });
         }
         pub fn get_cell_adjacent_cells(&self, cell_id: &Uuid) -> Vec<&Cell> {
@@ -114,9 +116,7 @@ pub mod graph {
         pub fn get_cell_elevation(&self, cell_id: &Uuid) -> f32 {
             let corners = self.get_cell_corners(cell_id);
             let l = corners.len() as f32;
-            return corners
-                .iter()
-                .fold(0.0, |acc, corner| 
+            return corners.iter().fold(0.0, |acc, corner|
                     // TODO - move to navtive corner elevation
                     acc + (corner.data.elevation / l));
         }
@@ -156,8 +156,19 @@ pub mod graph {
         }
     }
 
-    pub fn generate_base_diagram(i: usize, x_scale: f64, y_scale: f64) -> Graph {
-        let rtn = initialise_voronoi(i, x_scale, y_scale, 5);
+    pub fn generate_base_diagram(
+        i: usize,
+        x_scale: f64,
+        y_scale: f64,
+    ) -> Result<Graph, MapGenError> {
+        let config = GenerationConfig {
+            cell_count: i,
+            x_scale,
+            y_scale,
+            relax_tolerance: 0.0,
+            ..Default::default()
+        };
+        let (rtn, _relaxation) = initialise_voronoi(&config, &mut rand::thread_rng())?;
         let mut graph = Graph {
             cells: HashMap::new(),
             edges: HashMap::new(),
@@ -186,19 +197,18 @@ pub mod graph {
             for point in points {
                 let pos = (point.x as f32, point.y as f32);
                 let existing_corner = graph.corners.values().find(|corner| corner.pos.eq(&pos));
-                if existing_corner.is_some() {
-                    let corner_id = existing_corner.unwrap().id.clone();
-                    cell_corner_ids.push(corner_id.clone());
+                if let Some(existing_corner) = existing_corner {
+                    let corner_id = existing_corner.id;
+                    cell_corner_ids.push(corner_id);
                     let corner_mut = graph.corners.get_mut(&corner_id).unwrap();
-                    corner_mut.cells.push(cell_id.clone());
-                    drop(corner_mut);
-                    graph_cell.corners.push(corner_id.clone());
+                    corner_mut.cells.push(cell_id);
+                    graph_cell.corners.push(corner_id);
                 } else {
                     let corner = Corner {
                         id: Uuid::new_v4(),
-                        cells: vec![cell_id.clone()],
+                        cells: vec![cell_id],
                         edges: Vec::new(),
-                        pos: pos,
+                        pos,
                         data: WorldData {
                             ocean: false,
                             water: false,
@@ -210,7 +220,7 @@ pub mod graph {
                         },
                         elevation: 0.0,
                     };
-                    cell_corner_ids.push(corner.id.clone());
+                    cell_corner_ids.push(corner.id);
                     graph_cell.corners.push(corner.id);
                     graph.corners.insert(corner.id, corner);
                 }
@@ -219,16 +229,16 @@ pub mod graph {
             let final_point: Option<&Uuid> = cell_corner_ids.last();
             let mut prev_point: Option<&Uuid> = None;
             for id in &cell_corner_ids {
-                let new_edge = if prev_point.is_none() {
+                let new_edge = if let Some(prev_point) = prev_point {
+                    // all other cases
+                    (id, prev_point)
+                } else {
                     // first case
                     (final_point.unwrap(), id)
-                } else {
-                    // all other cases
-                    (id, prev_point.unwrap())
                 };
                 let new_edge_corners = (
-                    graph.corners.get(&new_edge.0).unwrap(),
-                    graph.corners.get(&new_edge.1).unwrap(),
+                    graph.corners.get(new_edge.0).unwrap(),
+                    graph.corners.get(new_edge.1).unwrap(),
                 );
                 let existing_edge = graph
                     .edges
@@ -236,21 +246,17 @@ pub mod graph {
                     .or(graph
                         .edges
                         .get(&(new_edge_corners.1.id, new_edge_corners.0.id)));
-                if existing_edge.is_some() {
-                    let key = existing_edge.unwrap().corners.clone();
-                    graph_cell.edges.push(key.clone());
-                    drop(existing_edge);
+                if let Some(existing_edge) = existing_edge {
+                    let key = existing_edge.corners;
+                    graph_cell.edges.push(key);
                     let existing_edge_mut = graph.edges.get_mut(&key).unwrap();
-                    existing_edge_mut.cells.push(cell_id.clone());
-                    drop(existing_edge_mut);
+                    existing_edge_mut.cells.push(cell_id);
                 } else {
-                    drop(existing_edge);
-                    let key = (new_edge_corners.0.id.clone(), new_edge_corners.1.id.clone());
-                    drop(new_edge_corners);
+                    let key = (new_edge_corners.0.id, new_edge_corners.1.id);
                     let edge = Edge {
                         id: Uuid::new_v4(),
-                        corners: key.clone(),
-                        cells: vec![cell_id.clone()],
+                        corners: key,
+                        cells: vec![cell_id],
                         data: WorldData {
                             ocean: false,
                             water: false,
@@ -260,23 +266,20 @@ pub mod graph {
                             moisture: 0.0,
                             biome: Biome::Bare,
                         },
-                        down_corner: key.0.clone(),
+                        down_corner: key.0,
                         noisey_midpoints: Vec::new(),
                         river: 0.0,
                     };
-                    graph_cell.edges.push(edge.corners.clone());
-                    graph.edges.insert(
-                        (new_edge_corners.0.id.clone(), new_edge_corners.1.id.clone()).clone(),
-                        edge.clone(),
-                    );
+                    graph_cell.edges.push(edge.corners);
+                    graph
+                        .edges
+                        .insert((new_edge_corners.0.id, new_edge_corners.1.id), edge.clone());
 
                     let corner_1 = graph.corners.get_mut(&key.0).unwrap();
-                    corner_1.edges.push(key.clone());
-                    drop(corner_1);
+                    corner_1.edges.push(key);
 
                     let corner_2 = graph.corners.get_mut(&key.1).unwrap();
-                    corner_2.edges.push(key.clone());
-                    drop(corner_2);
+                    corner_2.edges.push(key);
                 }
                 prev_point = Some(id);
             }
@@ -287,7 +290,7 @@ pub mod graph {
         let cell_ids = cells_clone.keys();
         // Cell centers
         for id in cell_ids {
-            let cell = graph.cells.get_mut(&id).unwrap();
+            let cell = graph.cells.get_mut(id).unwrap();
             let center = cell.corners.iter().fold((0.0, 0.0), |acc, corner| {
                 let pos = graph.corners.get(corner).unwrap();
                 return (
@@ -296,10 +299,8 @@ pub mod graph {
                 );
             });
             cell.center = center;
-
-            drop(cell);
         }
-        return graph;
+        return Ok(graph);
     }
 
     #[cfg(test)]
@@ -308,13 +309,13 @@ pub mod graph {
 
         #[test]
         fn gen_base_graph_test() {
-            let graph = generate_base_diagram(500, 100.0, 200.0);
+            let graph = generate_base_diagram(500, 100.0, 200.0).unwrap();
             for edge_id in graph.edges.keys() {
-                let edge_cells = graph.get_edge_cells(&edge_id);
-                assert!(edge_cells.len().eq(&2)||edge_cells.len().eq(&1));
+                let edge_cells = graph.get_edge_cells(edge_id);
+                assert!(edge_cells.len().eq(&2) || edge_cells.len().eq(&1));
             }
             for (cell_id, cell) in graph.cells.iter() {
-                let adjacent_cells = graph.get_cell_adjacent_cells(&cell_id);
+                let adjacent_cells = graph.get_cell_adjacent_cells(cell_id);
                 assert!(adjacent_cells.len() <= cell.edges.len());
             }
         }
@@ -1,10 +1,22 @@
+pub mod mask;
+pub mod save;
+pub mod spatial;
+pub mod sphere;
+pub mod validation;
 pub mod graph2 {
-    use std::collections::{HashMap, HashSet};
-    use uuid::Uuid;
+    use rand::Rng;
+    use std::collections::{BTreeSet, HashMap};
+    use std::ops::{Index, IndexMut};
 
-    use crate::{helpers::helpers::create_benchmarker, voronoi::voronoi::initialise_voronoi};
+    use crate::{
+        config::config::GenerationConfig,
+        error::error::MapGenError,
+        graph2::mask::mask::BoundaryMask,
+        graph2::spatial::spatial::SpatialIndex,
+        voronoi::voronoi::{initialise_voronoi, primary_cells, RelaxationReport},
+    };
 
-    #[derive(Debug, Clone)]
+    #[derive(Debug, Clone, PartialEq)]
     pub enum Biome {
         Ocean,
         Lake,
@@ -25,27 +37,74 @@ pub mod graph2 {
         SubtropicalDesert,
     }
 
-    #[derive(Debug, Clone)]
+    impl Biome {
+        /// Every biome. Saved maps store a biome by its position here, so new
+        /// biomes must only ever be appended
+        pub const ALL: [Biome; 17] = [
+            Biome::Ocean,
+            Biome::Lake,
+            Biome::Beach,
+            Biome::Marsh,
+            Biome::Ice,
+            Biome::Snow,
+            Biome::Tundra,
+            Biome::Taiga,
+            Biome::Bare,
+            Biome::Shrubland,
+            Biome::TemperateDesert,
+            Biome::TemperateRainForest,
+            Biome::TemperateForest,
+            Biome::Grassland,
+            Biome::TropicalRainForest,
+            Biome::TropicalForest,
+            Biome::SubtropicalDesert,
+        ];
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+    pub struct CornerId(pub u32);
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+    pub struct EdgeId(pub u32);
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+    pub struct CellId(pub u32);
+
+    impl CornerId {
+        pub fn index(self) -> usize {
+            return self.0 as usize;
+        }
+    }
+    impl EdgeId {
+        pub fn index(self) -> usize {
+            return self.0 as usize;
+        }
+    }
+    impl CellId {
+        pub fn index(self) -> usize {
+            return self.0 as usize;
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
     pub struct Corner {
         // Graph Data
         pub pos: (f32, f32),
-        pub edges: Vec<Uuid>,
+        pub edges: Vec<EdgeId>,
         // Terrain Data
         pub elevation: f32,
     }
-    #[derive(Debug, Clone)]
+    #[derive(Debug, Clone, PartialEq)]
     pub struct Edge {
         // Graph Data
-        pub corners: (Uuid, Uuid),
+        pub corners: (CornerId, CornerId),
         pub corner_midpoints: Vec<(f32, f32)>,
-        pub cells: Vec<Uuid>,
+        pub cells: Vec<CellId>,
         // Terrain Data
         pub river: f32,
     }
-    #[derive(Debug, Clone)]
+    #[derive(Debug, Clone, PartialEq)]
     pub struct Cell {
         // Graph Data
-        pub edges: Vec<Uuid>,
+        pub edges: Vec<EdgeId>,
         // Terrain Data
         pub water: bool,
         pub ocean: bool,
@@ -53,66 +112,131 @@ pub mod graph2 {
         pub moisture: f32,
         pub biome: Biome,
     }
-    #[derive(Debug, Clone)]
+    /// Corners, edges and cells are stored in dense arenas, addressed by the
+    /// typed ids above. Index the graph directly, e.g. `graph[cell_id]`.
+    #[derive(Debug, Clone, PartialEq)]
     pub struct Graph {
-        pub corners: HashMap<Uuid, Corner>,
-        pub edges: HashMap<Uuid, Edge>,
-        pub cells: HashMap<Uuid, Cell>,
+        pub corners: Vec<Corner>,
+        pub edges: Vec<Edge>,
+        pub cells: Vec<Cell>,
+        // Bounds the graph was generated within
+        pub x_scale: f64,
+        pub y_scale: f64,
+        /// The east and west edges join, so positions are only meaningful
+        /// modulo `x_scale`. See `wrap_pos` and `unwrap_pos`
+        pub wrap_x: bool,
+        /// Built on a sphere, with positions in equirectangular projection.
+        /// Spherical graphs also set `wrap_x`
+        pub sphere: bool,
+        /// Region of the bounds the map fills, cells outside it become ocean
+        pub boundary: BoundaryMask,
+        /// Lookup grid behind `cell_at`, `nearest_corner` and `nearest_edge`
+        pub spatial_index: SpatialIndex,
+    }
+
+    impl Index<CornerId> for Graph {
+        type Output = Corner;
+        fn index(&self, id: CornerId) -> &Corner {
+            return &self.corners[id.index()];
+        }
+    }
+    impl IndexMut<CornerId> for Graph {
+        fn index_mut(&mut self, id: CornerId) -> &mut Corner {
+            return &mut self.corners[id.index()];
+        }
+    }
+    impl Index<EdgeId> for Graph {
+        type Output = Edge;
+        fn index(&self, id: EdgeId) -> &Edge {
+            return &self.edges[id.index()];
+        }
+    }
+    impl IndexMut<EdgeId> for Graph {
+        fn index_mut(&mut self, id: EdgeId) -> &mut Edge {
+            return &mut self.edges[id.index()];
+        }
+    }
+    impl Index<CellId> for Graph {
+        type Output = Cell;
+        fn index(&self, id: CellId) -> &Cell {
+            return &self.cells[id.index()];
+        }
+    }
+    impl IndexMut<CellId> for Graph {
+        fn index_mut(&mut self, id: CellId) -> &mut Cell {
+            return &mut self.cells[id.index()];
+        }
     }
 
     impl Graph {
+        // ids
+        pub fn corner_ids(&self) -> impl Iterator<Item = CornerId> {
+            return (0..self.corners.len() as u32).map(CornerId);
+        }
+        pub fn edge_ids(&self) -> impl Iterator<Item = EdgeId> {
+            return (0..self.edges.len() as u32).map(EdgeId);
+        }
+        pub fn cell_ids(&self) -> impl Iterator<Item = CellId> {
+            return (0..self.cells.len() as u32).map(CellId);
+        }
         // cells
-        pub fn get_cell_edges(&self, id: &Uuid) -> Vec<&Edge> {
-            let cell = &self.cells.get(id).unwrap();
-            return cell
-                .edges
-                .iter()
-                .map(|e_id| self.edges.get(e_id).unwrap())
-                .collect();
+        pub fn get_cell_edges(&self, id: CellId) -> Vec<&Edge> {
+            return self[id].edges.iter().map(|e_id| &self[*e_id]).collect();
         }
-        pub fn get_cell_corners(&self, id: &Uuid) -> Vec<&Corner> {
-            let cell = &self.cells.get(id).unwrap();
+        pub fn get_cell_corners(&self, id: CellId) -> Vec<&Corner> {
             let mut output: Vec<&Corner> = Vec::new();
-            for edge in &cell.edges {
-                let (c_1, c_2) = self.get_edge_corners(edge);
+            for edge_id in &self[id].edges {
+                let (c_1, c_2) = self.get_edge_corners(*edge_id);
                 output.push(c_1);
                 output.push(c_2);
             }
             return output;
         }
-        pub fn get_cell_elevation(&self, cell_id: &Uuid) -> f32 {
-            let cell = self.cells.get(cell_id).unwrap();
+        pub fn get_cell_elevation(&self, cell_id: CellId) -> f32 {
+            let cell = &self[cell_id];
             let edge_count = cell.edges.len();
             return cell.edges.iter().fold(0.0, |acc, edge_id| {
-                acc + (self.get_edge_elevation(edge_id) / edge_count as f32)
+                acc + (self.get_edge_elevation(*edge_id) / edge_count as f32)
             });
         }
-        pub fn get_cell_corners_ids(&self, id: &Uuid) -> HashSet<&Uuid> {
-            let cell = &self.cells.get(id).unwrap();
-            let mut output: HashSet<&Uuid> = HashSet::new();
-            for edge_id in &cell.edges {
-                let edge = self.edges.get(edge_id).unwrap();
-                output.insert(&edge.corners.0);
-                output.insert(&edge.corners.1);
+        pub fn get_cell_corners_ids(&self, id: CellId) -> BTreeSet<CornerId> {
+            let mut output: BTreeSet<CornerId> = BTreeSet::new();
+            for edge_id in &self[id].edges {
+                let edge = &self[*edge_id];
+                output.insert(edge.corners.0);
+                output.insert(edge.corners.1);
             }
             return output;
         }
-        pub fn get_cell_adjacent_cells(&self, id: &Uuid) -> Vec<&Uuid> {
-            let cell = self.cells.get(id).unwrap();
-            let mut output: Vec<&Uuid> = Vec::new();
-            for edge_id in &cell.edges {
-                let edge = self.edges.get(edge_id).unwrap();
-                for cell_id in &edge.cells {
-                    if !cell_id.eq(id) {
-                        output.push(&cell_id);
+        pub fn get_cell_adjacent_cells(&self, id: CellId) -> Vec<CellId> {
+            let mut output: Vec<CellId> = Vec::new();
+            for edge_id in &self[id].edges {
+                for cell_id in &self[*edge_id].cells {
+                    if !cell_id.eq(&id) {
+                        output.push(*cell_id);
                     }
                 }
             }
             return output;
         }
-        pub fn get_cell_center(&self, id: &Uuid) -> (f32, f32) {
+        pub fn get_cell_center(&self, id: CellId) -> (f32, f32) {
+            if self.sphere {
+                return self.project_3d(self.get_cell_center_3d(id));
+            }
             let corners = self.get_cell_corners(id);
             let corners_len = corners.len();
+            if self.wrap_x && corners_len > 0 {
+                // average on one side of the seam, then fold the result back in
+                let reference = corners[0].pos;
+                let center = corners.iter().fold((0.0, 0.0), |(x, y), corner| {
+                    let pos = self.unwrap_pos(reference, corner.pos);
+                    (
+                        x + (pos.0 / corners_len as f32),
+                        y + (pos.1 / corners_len as f32),
+                    )
+                });
+                return self.wrap_pos(center);
+            }
             return corners.iter().fold((0.0, 0.0), |(x, y), corner| {
                 (
                     x + (corner.pos.0 / corners_len as f32),
@@ -120,32 +244,164 @@ pub mod graph2 {
                 )
             });
         }
-        pub fn get_cell_corners_in_order(&self, id: &Uuid) -> Vec<&Corner> {
-            let cell = &self.cells.get(id).unwrap();
-            let mut working_edges = cell.edges.clone();
-            // println!("{:?}", working_edges.len());
-            if working_edges.len().eq(&0) {
-                return vec![];
+
+        /// Moves each point onto the same side of the seam as the point before
+        /// it, so a line crossing the seam stays in one piece.
+        pub fn unwrap_path(&self, points: Vec<(f32, f32)>) -> Vec<(f32, f32)> {
+            let mut output: Vec<(f32, f32)> = Vec::with_capacity(points.len() + 3);
+            for pos in points {
+                let next = match output.last() {
+                    Some(previous) => self.unwrap_pos(*previous, pos),
+                    None => pos,
+                };
+                output.push(next);
+            }
+            return output;
+        }
+
+        /// Chains `points` into one unbroken 2D outline, shifting each across
+        /// the seam to sit beside the one before it. A ring that circles a pole
+        /// of a spherical map is closed along the top or bottom of the map.
+        pub fn unwrap_outline(&self, points: Vec<(f32, f32)>) -> Vec<(f32, f32)> {
+            let mut outline = self.unwrap_path(points);
+            if let (Some(first), Some(last)) = (outline.first().copied(), outline.last().copied()) {
+                // going once around a pole leaves the ring a map width from its start
+                let wrapped_first = self.unwrap_pos(last, first);
+                if wrapped_first != first {
+                    let mean_y = outline.iter().map(|p| p.1).sum::<f32>() / outline.len() as f32;
+                    let pole_y = if mean_y > self.y_scale as f32 / 2.0 {
+                        self.y_scale as f32
+                    } else {
+                        0.0
+                    };
+                    outline.push(wrapped_first);
+                    outline.push((wrapped_first.0, pole_y));
+                    outline.push((first.0, pole_y));
+                }
+            }
+            return outline;
+        }
+
+        /// Horizontal shifts to draw an unwrapped shape at, adding a second
+        /// copy on the far side when it hangs off either edge of a wrapping map
+        pub fn get_seam_offsets(&self, points: &[(f32, f32)]) -> Vec<f32> {
+            let mut offsets = vec![0.0];
+            if self.wrap_x {
+                let width = self.x_scale as f32;
+                if points.iter().any(|p| p.0 > width) {
+                    offsets.push(-width);
+                }
+                if points.iter().any(|p| p.0 < 0.0) {
+                    offsets.push(width);
+                }
+            }
+            return offsets;
+        }
+
+        pub fn get_cell_outline(&self, id: CellId) -> Result<Vec<(f32, f32)>, MapGenError> {
+            let corners = self.get_cell_corners_in_order(id)?;
+            return Ok(self.unwrap_outline(corners.iter().map(|c| c.pos).collect()));
+        }
+
+        /// Like `get_cell_outline`, but following each edge through its
+        /// `corner_midpoints` once `add_edge_divisions` has run
+        pub fn get_cell_outline_with_midpoints(
+            &self,
+            id: CellId,
+        ) -> Result<Vec<(f32, f32)>, MapGenError> {
+            let mut working_edges = self[id].edges.clone();
+            if working_edges.is_empty() {
+                return Ok(vec![]);
+            }
+            let first_edge_id = working_edges.remove(0);
+            let (first_corner, mut last_corner) = self[first_edge_id].corners;
+            let mut points = self.get_edge_path_from(first_edge_id, first_corner);
+            while !working_edges.is_empty() {
+                let index = working_edges
+                    .iter()
+                    .position(|e_id| self.edges_share_corner(*e_id, last_corner))
+                    .ok_or(MapGenError::BrokenCellRing(id))?;
+                let edge_id = working_edges.remove(index);
+                // the first point is the corner the previous edge ended on
+                points.extend(&self.get_edge_path_from(edge_id, last_corner)[1..]);
+                last_corner = self.get_edge_other_corner(edge_id, last_corner);
+            }
+            if last_corner != first_corner {
+                return Err(MapGenError::BrokenCellRing(id));
+            }
+            points.pop();
+            return Ok(self.unwrap_outline(points));
+        }
+
+        /// Folds a position that has wandered past the seam back into the map.
+        /// Positions are returned unchanged when the map does not wrap.
+        pub fn wrap_pos(&self, pos: (f32, f32)) -> (f32, f32) {
+            if !self.wrap_x {
+                return pos;
+            }
+            let x_scale = self.x_scale as f32;
+            let x = pos.0.rem_euclid(x_scale);
+            return (if x >= x_scale { 0.0 } else { x }, pos.1);
+        }
+
+        /// Shifts `pos` by a whole map width if that brings it closer to
+        /// `reference`, so shapes crossing the seam can be drawn or measured
+        /// without jumping across the map.
+        pub fn unwrap_pos(&self, reference: (f32, f32), pos: (f32, f32)) -> (f32, f32) {
+            if !self.wrap_x {
+                return pos;
+            }
+            let x_scale = self.x_scale as f32;
+            let dx = pos.0 - reference.0;
+            if dx > x_scale / 2.0 {
+                return (pos.0 - x_scale, pos.1);
+            } else if dx < -x_scale / 2.0 {
+                return (pos.0 + x_scale, pos.1);
+            }
+            return pos;
+        }
+
+        /// Straight line distance, measured across the seam when that is
+        /// shorter. On spherical maps this is the great circle distance
+        pub fn pos_distance(&self, a: (f32, f32), b: (f32, f32)) -> f32 {
+            if self.sphere {
+                return self.sphere_distance(a, b);
+            }
+            let b = self.unwrap_pos(a, b);
+            let x_dist = (a.0 - b.0).abs();
+            let y_dist = (a.1 - b.1).abs();
+            return (x_dist.powi(2) + y_dist.powi(2)).sqrt();
+        }
+        pub fn get_cell_corners_in_order(&self, id: CellId) -> Result<Vec<&Corner>, MapGenError> {
+            let corner_ids = self.get_cell_corner_ids_in_order(id)?;
+            return Ok(corner_ids.iter().map(|c_id| &self[*c_id]).collect());
+        }
+        pub fn get_cell_corner_ids_in_order(
+            &self,
+            id: CellId,
+        ) -> Result<Vec<CornerId>, MapGenError> {
+            let mut working_edges = self[id].edges.clone();
+            if working_edges.is_empty() {
+                return Ok(vec![]);
             }
-            let mut output_corners: Vec<Uuid> = Vec::new();
-            let last_edge_id = working_edges.remove(0);
-            let starting_edge = &self.edges.get(&last_edge_id).unwrap();
+            let mut output_corners: Vec<CornerId> = Vec::new();
+            let starting_edge = &self[working_edges.remove(0)];
             output_corners.push(starting_edge.corners.0);
             output_corners.push(starting_edge.corners.1);
-            while working_edges.len() > 0 {
-                let mut last_corner = output_corners.last().unwrap();
+            while !working_edges.is_empty() {
+                let mut last_corner = *output_corners.last().unwrap();
                 let mut next_edge_id_option = working_edges
                     .iter()
-                    .find(|e_id| self.edges_share_corner(e_id, last_corner));
+                    .find(|e_id| self.edges_share_corner(**e_id, last_corner));
                 if next_edge_id_option.is_none() {
                     output_corners.reverse();
-                    last_corner = output_corners.last().unwrap();
+                    last_corner = *output_corners.last().unwrap();
                     next_edge_id_option = working_edges
                         .iter()
-                        .find(|e_id| self.edges_share_corner(e_id, last_corner));
+                        .find(|e_id| self.edges_share_corner(**e_id, last_corner));
                 }
-                let next_edge_id = next_edge_id_option.unwrap().clone();
-                let edge = self.edges.get(&next_edge_id).unwrap();
+                let next_edge_id = *next_edge_id_option.ok_or(MapGenError::BrokenCellRing(id))?;
+                let edge = &self[next_edge_id];
                 if !output_corners.contains(&edge.corners.0) {
                     output_corners.push(edge.corners.0);
                 }
@@ -156,21 +412,16 @@ pub mod graph2 {
 
                 working_edges.retain(|e_id| !e_id.eq(&next_edge_id));
             }
-            return output_corners
-                .iter()
-                .map(|c_id| self.corners.get(c_id).unwrap())
-                .collect();
+            return Ok(output_corners);
         }
         // edges
-        fn get_edge_corners(&self, id: &Uuid) -> (&Corner, &Corner) {
-            let edge = &self.edges.get(id).unwrap();
-            let c_1 = self.corners.get(&edge.corners.0).unwrap();
-            let c_2 = self.corners.get(&edge.corners.1).unwrap();
-            return (c_1, c_2);
-        }
-        pub fn edges_share_same_corners(&self, id_1: &Uuid, index: usize, id_2: &Uuid) -> bool {
-            let e_1 = self.edges.get(id_1).unwrap();
-            let e_2 = self.edges.get(id_2).unwrap();
+        fn get_edge_corners(&self, id: EdgeId) -> (&Corner, &Corner) {
+            let edge = &self[id];
+            return (&self[edge.corners.0], &self[edge.corners.1]);
+        }
+        pub fn edges_share_same_corners(&self, id_1: EdgeId, index: usize, id_2: EdgeId) -> bool {
+            let e_1 = &self[id_1];
+            let e_2 = &self[id_2];
             if index.eq(&0) {
                 return (e_1.corners.0.eq(&e_2.corners.0)) || (e_2.corners.0.eq(&e_2.corners.1));
             }
@@ -179,79 +430,91 @@ pub mod graph2 {
             }
             return false;
         }
-        pub fn edge_shares_pos_at(&self, id: &Uuid, pos: &(f32, f32)) -> bool {
-            let edge = self.edges.get(id).unwrap();
-
-            let c_1 = self.corners.get(&edge.corners.0).unwrap();
-            let c_2 = self.corners.get(&edge.corners.1).unwrap();
+        pub fn edge_shares_pos_at(&self, id: EdgeId, pos: &(f32, f32)) -> bool {
+            let (c_1, c_2) = self.get_edge_corners(id);
             return c_1.pos.eq(pos) || c_2.pos.eq(pos);
         }
-        fn edges_share_corner(&self, edge_id: &Uuid, corner_id: &Uuid) -> bool {
-            let edge = self.edges.get(edge_id).unwrap();
-            return edge.corners.0.eq(corner_id) || edge.corners.1.eq(corner_id);
+        fn edges_share_corner(&self, edge_id: EdgeId, corner_id: CornerId) -> bool {
+            let edge = &self[edge_id];
+            return edge.corners.0.eq(&corner_id) || edge.corners.1.eq(&corner_id);
+        }
+        /// The corner at the far end of the edge from `corner_id`
+        pub fn get_edge_other_corner(&self, edge_id: EdgeId, corner_id: CornerId) -> CornerId {
+            let (c_1, c_2) = self[edge_id].corners;
+            return if c_1.eq(&corner_id) { c_2 } else { c_1 };
         }
-        pub fn get_edge_elevation(&self, edge_id: &Uuid) -> f32 {
-            let edge = self.edges.get(edge_id).unwrap();
-            let c1 = self.corners.get(&edge.corners.0).unwrap();
-            let c2 = self.corners.get(&edge.corners.1).unwrap();
+        /// The edge's `corner_midpoints` ordered away from `start`, or just its
+        /// two corners before `add_edge_divisions` has run
+        pub fn get_edge_path_from(&self, edge_id: EdgeId, start: CornerId) -> Vec<(f32, f32)> {
+            let edge = &self[edge_id];
+            let mut path = if edge.corner_midpoints.is_empty() {
+                vec![self[edge.corners.0].pos, self[edge.corners.1].pos]
+            } else {
+                edge.corner_midpoints.clone()
+            };
+            if edge.corners.1.eq(&start) {
+                path.reverse();
+            }
+            return path;
+        }
+        pub fn get_edge_elevation(&self, edge_id: EdgeId) -> f32 {
+            let (c1, c2) = self.get_edge_corners(edge_id);
             return (c1.elevation + c2.elevation) / 2.0;
         }
-        pub fn get_edge_downwards_corner(&self, edge_id: &Uuid) -> (&Uuid, &Corner) {
-            let edge = self.edges.get(edge_id).unwrap();
-            let c1 = self.corners.get(&edge.corners.0).unwrap();
-            let c2 = self.corners.get(&edge.corners.1).unwrap();
+        pub fn get_edge_downwards_corner(&self, edge_id: EdgeId) -> (CornerId, &Corner) {
+            let edge = &self[edge_id];
+            let (c1, c2) = self.get_edge_corners(edge_id);
             if c1.elevation < c2.elevation {
-                return (&edge.corners.0, c1);
+                return (edge.corners.0, c1);
             } else {
-                return (&edge.corners.1, c2);
+                return (edge.corners.1, c2);
             }
         }
-        pub fn edge_is_coastal(&self, edge_id: &Uuid) -> bool {
-            let edge = self.edges.get(edge_id).unwrap();
-            let cells: Vec<&Cell> = edge
+        pub fn edge_is_coastal(&self, edge_id: EdgeId) -> bool {
+            let cells: Vec<&Cell> = self[edge_id]
                 .cells
                 .iter()
-                .map(|cell_id| self.cells.get(cell_id).unwrap())
+                .map(|cell_id| &self[*cell_id])
                 .collect();
             return cells.iter().any(|cell| !cell.water) && cells.iter().any(|cell| cell.water);
         }
         // corners
-        pub fn get_corner_cells(&self, corner_id: &Uuid) -> Vec<(Uuid, &Cell)> {
-            let corner = self.corners.get(corner_id).unwrap();
-            let edge_cells: Vec<Vec<Uuid>> = corner
-                .edges
-                .iter()
-                .map(|e_id| self.edges.get(&e_id).unwrap().cells.clone())
-                .collect();
-            return edge_cells
-                .concat()
-                .iter()
-                .map(|c_id| (c_id.clone(), self.cells.get(c_id).unwrap()))
-                .collect();
+        pub fn get_corner_cells(&self, corner_id: CornerId) -> Vec<(CellId, &Cell)> {
+            let mut output: Vec<(CellId, &Cell)> = Vec::new();
+            for edge_id in &self[corner_id].edges {
+                for cell_id in &self[*edge_id].cells {
+                    output.push((*cell_id, &self[*cell_id]));
+                }
+            }
+            return output;
         }
-        pub fn get_corner_adjacent_corners(&self, corner_id: &Uuid) -> Vec<(Uuid, &Corner)> {
-            let corner = self.corners.get(corner_id).unwrap();
-            let edge_cells: Vec<(Uuid, Uuid)> = corner
-                .edges
-                .iter()
-                .map(|e_id| self.edges.get(&e_id).unwrap().corners)
-                .collect();
-            let mut corner_id_set: HashSet<Uuid> = HashSet::new();
-            for (c1, c2) in edge_cells {
-                if !c1.eq(corner_id) {
+        pub fn get_corner_adjacent_corners(&self, corner_id: CornerId) -> Vec<(CornerId, &Corner)> {
+            let mut corner_id_set: BTreeSet<CornerId> = BTreeSet::new();
+            for edge_id in &self[corner_id].edges {
+                let (c1, c2) = self[*edge_id].corners;
+                if !c1.eq(&corner_id) {
                     corner_id_set.insert(c1);
                 }
-                if !c2.eq(corner_id) {
+                if !c2.eq(&corner_id) {
                     corner_id_set.insert(c2);
                 }
             }
             return corner_id_set
                 .iter()
-                .map(|c_id| (c_id.clone(), self.corners.get(c_id).unwrap()))
+                .map(|c_id| (*c_id, &self[*c_id]))
                 .collect();
         }
     }
 
+    // rem_euclid can round a tiny negative up to exactly `x_scale`
+    fn wrap_coordinate(x: f64, x_scale: f64) -> f64 {
+        let wrapped = x.rem_euclid(x_scale);
+        if wrapped >= x_scale {
+            return 0.0;
+        }
+        return wrapped;
+    }
+
     fn create_pos_key(x: f32, y: f32) -> String {
         let mut x_refined = x;
         if x.eq(&0.0) || x.eq(&-0.0) {
@@ -264,20 +527,51 @@ pub mod graph2 {
         return format!("{:.5}-{:.5}", x_refined, y_refined);
     }
 
-    pub fn generate_base_graph(i: usize, x_scale: f64, y_scale: f64) -> Graph {
-        let voron_init = create_benchmarker(String::from("Voronoi Init"));
-        let voronoi = initialise_voronoi(i, x_scale, y_scale, 5);
-        voron_init();
+    // fills in the edge to cell and corner to edge references from the cell
+    // to edge and edge to corner ones
+    pub(crate) fn link_back_references(graph: &mut Graph) {
+        for cell_id in graph.cell_ids() {
+            for i in 0..graph[cell_id].edges.len() {
+                let e_id = graph[cell_id].edges[i];
+                graph[e_id].cells.push(cell_id);
+            }
+        }
+        for edge_id in graph.edge_ids() {
+            let (c_1_id, c_2_id) = graph[edge_id].corners;
+            graph[c_1_id].edges.push(edge_id);
+            graph[c_2_id].edges.push(edge_id);
+        }
+    }
+
+    pub fn generate_base_graph(
+        config: &GenerationConfig,
+        rng: &mut impl Rng,
+    ) -> Result<Graph, MapGenError> {
+        let (graph, _relaxation) = build_base_graph(config, rng)?;
+        return Ok(graph);
+    }
+
+    /// `generate_base_graph`, along with how the sites were relaxed
+    pub(crate) fn build_base_graph(
+        config: &GenerationConfig,
+        rng: &mut impl Rng,
+    ) -> Result<(Graph, RelaxationReport), MapGenError> {
+        config.validate()?;
+        let (voronoi, relaxation) = initialise_voronoi(config, rng)?;
         let mut graph = Graph {
-            cells: HashMap::new(),
-            edges: HashMap::new(),
-            corners: HashMap::new(),
+            cells: Vec::new(),
+            edges: Vec::new(),
+            corners: Vec::new(),
+            x_scale: config.x_scale,
+            y_scale: config.y_scale,
+            wrap_x: config.wrap_x,
+            sphere: false,
+            boundary: config.boundary.clone(),
+            spatial_index: SpatialIndex::default(),
         };
-        let mut point_cache: HashMap<String, Uuid> = HashMap::new();
-        let mut edge_cache: HashMap<String, Uuid> = HashMap::new();
-        let cell_init = create_benchmarker(String::from("Cell Init"));
-        for cell in voronoi.cells() {
-            let cell_id = Uuid::new_v4();
+        let mut point_cache: HashMap<String, CornerId> = HashMap::new();
+        let mut edge_cache: HashMap<String, EdgeId> = HashMap::new();
+        for cell in primary_cells(&voronoi, config.wrap_x) {
             let mut graph_cell = Cell {
                 edges: Vec::new(),
                 water: false,
@@ -287,51 +581,66 @@ pub mod graph2 {
                 coast: false,
             };
 
-            let first_point = cell.points().first().unwrap();
-            let mut previous_point: Option<Uuid> = None;
-            for point in cell.points() {
+            // cells along the seam reach past the map edge, folding them back
+            // in lets both sides of the seam share the same corners
+            let points: Vec<(f32, f32)> = cell
+                .points()
+                .iter()
+                .map(|p| {
+                    if config.wrap_x {
+                        return (wrap_coordinate(p.x, config.x_scale) as f32, p.y as f32);
+                    }
+                    return (p.x as f32, p.y as f32);
+                })
+                .collect();
+            let Some(first_point) = points.first() else {
+                return Err(MapGenError::DegeneratePoints(String::from(
+                    "voronoi cell has no points",
+                )));
+            };
+            let mut previous_point: Option<CornerId> = None;
+            for point in &points {
                 // set up corner
-                let cache_search = point_cache.get(&create_pos_key(point.x as f32, point.y as f32));
+                let cache_search = point_cache.get(&create_pos_key(point.0, point.1));
 
-                let corner_id = if cache_search.is_some() {
-                    cache_search.unwrap().clone()
+                let corner_id = if let Some(cache_search) = cache_search {
+                    *cache_search
                 } else {
-                    let id = Uuid::new_v4();
-                    let corner = Corner {
-                        pos: (point.x as f32, point.y as f32),
+                    let id = CornerId(graph.corners.len() as u32);
+                    graph.corners.push(Corner {
+                        pos: *point,
                         edges: Vec::new(),
                         elevation: 0.0,
-                    };
-                    graph.corners.insert(id, corner);
-                    point_cache.insert(create_pos_key(point.x as f32, point.y as f32), id.clone());
-                    id.clone()
+                    });
+                    point_cache.insert(create_pos_key(point.0, point.1), id);
+                    id
                 };
                 // define edge
-                if point.eq(&first_point) || previous_point.is_none() {
+                if point.eq(first_point) || previous_point.is_none() {
                     previous_point = Some(corner_id);
                     continue;
                 }
-                let prev_corner = graph.corners.get(&previous_point.unwrap()).unwrap();
+                let prev_corner = &graph[previous_point.unwrap()];
                 let c1_search = edge_cache.get(&format!(
                     "{}{}",
                     create_pos_key(prev_corner.pos.0, prev_corner.pos.1),
-                    create_pos_key(point.x as f32, point.y as f32)
+                    create_pos_key(point.0, point.1)
                 ));
-                let edge_id = if c1_search.is_some() {
-                    c1_search.unwrap().clone()
+                let edge_id = if let Some(c1_search) = c1_search {
+                    *c1_search
                 } else {
-                    let edge_id = Uuid::new_v4();
-                    let edge = Edge {
-                        corners: (corner_id, previous_point.unwrap().clone()),
+                    let edge_id = EdgeId(graph.edges.len() as u32);
+                    graph.edges.push(Edge {
+                        corners: (corner_id, previous_point.unwrap()),
                         corner_midpoints: Vec::new(),
                         cells: Vec::new(),
                         river: 0.0,
-                    };
-                    graph.edges.insert(edge_id, edge);
+                    });
                     edge_id
                 };
                 graph_cell.edges.push(edge_id);
-                let corner = graph.corners.get(&corner_id).unwrap();
+                let prev_corner = &graph[previous_point.unwrap()];
+                let corner = &graph[corner_id];
                 edge_cache.insert(
                     format!(
                         "{}{}",
@@ -350,85 +659,112 @@ pub mod graph2 {
                 );
                 previous_point = Some(corner_id);
             }
-            let first_point = cell.points().first().unwrap();
-            let last_point = cell.points().last().unwrap();
+            let last_point = points.last().unwrap();
             let existing_edge = edge_cache.get(&format!(
                 "{}{}",
-                create_pos_key(first_point.x as f32, first_point.y as f32),
-                create_pos_key(last_point.x as f32, last_point.y as f32)
+                create_pos_key(first_point.0, first_point.1),
+                create_pos_key(last_point.0, last_point.1)
             ));
-            if existing_edge.is_some() {
-                graph_cell.edges.push(existing_edge.unwrap().clone());
+            if let Some(existing_edge) = existing_edge {
+                graph_cell.edges.push(*existing_edge);
             } else {
-                let edge_id = Uuid::new_v4();
-                let c_1 =
-                    point_cache.get(&create_pos_key(first_point.x as f32, first_point.y as f32));
-                let c_2 =
-                    point_cache.get(&create_pos_key(last_point.x as f32, last_point.y as f32));
-                if c_1.is_some() && c_2.is_some() {
-                    let edge = Edge {
-                        corners: (c_1.unwrap().clone(), c_2.unwrap().clone()),
+                let c_1 = point_cache.get(&create_pos_key(first_point.0, first_point.1));
+                let c_2 = point_cache.get(&create_pos_key(last_point.0, last_point.1));
+                if let (Some(c_1), Some(c_2)) = (c_1, c_2) {
+                    let edge_id = EdgeId(graph.edges.len() as u32);
+                    graph.edges.push(Edge {
+                        corners: (*c_1, *c_2),
                         corner_midpoints: Vec::new(),
                         cells: Vec::new(),
                         river: 0.0,
-                    };
-                    graph.edges.insert(edge_id, edge);
-                    graph_cell.edges.push(edge_id.clone());
+                    });
+                    graph_cell.edges.push(edge_id);
                     edge_cache.insert(
                         format!(
                             "{}{}",
-                            create_pos_key(first_point.x as f32, first_point.y as f32),
-                            create_pos_key(last_point.x as f32, last_point.y as f32)
+                            create_pos_key(first_point.0, first_point.1),
+                            create_pos_key(last_point.0, last_point.1)
                         ),
                         edge_id,
                     );
                     edge_cache.insert(
                         format!(
                             "{}{}",
-                            create_pos_key(last_point.x as f32, last_point.y as f32),
-                            create_pos_key(first_point.x as f32, first_point.y as f32),
+                            create_pos_key(last_point.0, last_point.1),
+                            create_pos_key(first_point.0, first_point.1),
                         ),
                         edge_id,
                     );
-                } else {
-                    println!("Edge Sealing lookup failure")
                 }
+                // a cell left unsealed here is reported as an open ring by validate below
             }
-            // TODO - handle last edge case, wrapping back to front
-            graph.cells.insert(cell_id, graph_cell);
-        }
-        cell_init();
-        let quick_ref_init = create_benchmarker(String::from("Quick Ref Init"));
-        // fill quick reference vectors
-        let graph_clone = graph.clone();
-        for (cell_id, cell) in graph_clone.cells {
-            for e_id in &cell.edges {
-                let edge = graph.edges.get_mut(&e_id).unwrap();
-                edge.cells.push(cell_id);
-                drop(edge);
-            }
+            graph.cells.push(graph_cell);
         }
-        for (edge_id, edge) in graph_clone.edges {
-            let (c_1_id, c_2_id) = edge.corners;
-            let corner_1 = graph.corners.get_mut(&c_1_id).unwrap();
-            corner_1.edges.push(edge_id);
-            drop(corner_1);
-            let corner_2 = graph.corners.get_mut(&c_2_id).unwrap();
-            corner_2.edges.push(edge_id);
-            drop(corner_2);
-        }
-        quick_ref_init();
-        println!("-----");
-        return graph;
+        link_back_references(&mut graph);
+        let violations = graph.validate();
+        if !violations.is_empty() {
+            return Err(MapGenError::InvalidGraph(violations));
+        }
+        graph.rebuild_spatial_index();
+        return Ok((graph, relaxation));
     }
 
     #[cfg(test)]
     mod tests {
+        use rand::{rngs::StdRng, SeedableRng};
+
         use super::*;
+        use crate::voronoi::voronoi::PointDistribution;
 
         #[test]
         fn gen_base_graph_test() {
-            generate_base_graph(500, 100.0, 200.0);
+            let config = GenerationConfig {
+                cell_count: 500,
+                x_scale: 100.0,
+                y_scale: 200.0,
+                ..Default::default()
+            };
+            generate_base_graph(&config, &mut StdRng::seed_from_u64(1)).unwrap();
+        }
+
+        #[test]
+        fn point_distributions_build_valid_graphs_test() {
+            let distributions = [
+                PointDistribution::PoissonDisk,
+                PointDistribution::JitteredGrid { jitter: 0.5 },
+                PointDistribution::HexGrid,
+            ];
+            for point_distribution in distributions {
+                for relax_factor in [0, 2] {
+                    let config = GenerationConfig {
+                        cell_count: 500,
+                        relax_factor,
+                        point_distribution: point_distribution.clone(),
+                        ..Default::default()
+                    };
+                    generate_base_graph(&config, &mut StdRng::seed_from_u64(2)).unwrap();
+                }
+            }
+        }
+
+        #[test]
+        fn adjacency_is_symmetric_test() {
+            let graph =
+                generate_base_graph(&GenerationConfig::default(), &mut StdRng::seed_from_u64(3))
+                    .unwrap();
+            for cell_id in graph.cell_ids() {
+                for n_cell_id in graph.get_cell_adjacent_cells(cell_id) {
+                    assert!(graph.get_cell_adjacent_cells(n_cell_id).contains(&cell_id));
+                }
+            }
+            for corner_id in graph.corner_ids() {
+                for (n_corner_id, _c) in graph.get_corner_adjacent_corners(corner_id) {
+                    assert!(graph
+                        .get_corner_adjacent_corners(n_corner_id)
+                        .iter()
+                        .any(|(id, _c)| id.eq(&corner_id)));
+                }
+            }
         }
     }
 }
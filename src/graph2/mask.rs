@@ -0,0 +1,74 @@
+pub mod mask {
+    use crate::graph2::spatial::spatial::polygon_contains;
+
+    /// The region of the `x_scale` × `y_scale` bounds a map is generated in.
+    /// Cells whose centre falls outside the mask are made ocean.
+    #[derive(Debug, Clone, PartialEq, Default)]
+    pub enum BoundaryMask {
+        /// The whole rectangle
+        #[default]
+        Rectangle,
+        /// A closed outline in map coordinates, such as a continent shape
+        Polygon(Vec<(f64, f64)>),
+        /// A grayscale image stretched over the map bounds, row 0 at the top
+        /// of the drawn map (the largest y). Pixels at or above `threshold`
+        /// are land
+        Image {
+            width: usize,
+            height: usize,
+            pixels: Vec<u8>,
+            threshold: u8,
+        },
+    }
+
+    impl BoundaryMask {
+        pub fn contains(&self, pos: (f32, f32), x_scale: f64, y_scale: f64) -> bool {
+            match self {
+                BoundaryMask::Rectangle => return true,
+                BoundaryMask::Polygon(points) => {
+                    let outline: Vec<(f32, f32)> =
+                        points.iter().map(|p| (p.0 as f32, p.1 as f32)).collect();
+                    return polygon_contains(&outline, pos);
+                }
+                BoundaryMask::Image {
+                    width,
+                    height,
+                    pixels,
+                    threshold,
+                } => {
+                    let u = pos.0 as f64 / x_scale;
+                    let v = 1.0 - pos.1 as f64 / y_scale;
+                    let column = ((u * *width as f64) as usize).min(width - 1);
+                    let row = ((v * *height as f64) as usize).min(height - 1);
+                    return pixels[row * width + column] >= *threshold;
+                }
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn mask_contains_test() {
+            assert!(BoundaryMask::Rectangle.contains((5.0, 5.0), 10.0, 10.0));
+
+            let triangle = BoundaryMask::Polygon(vec![(0.0, 0.0), (10.0, 0.0), (0.0, 10.0)]);
+            assert!(triangle.contains((2.0, 2.0), 10.0, 10.0));
+            assert!(!triangle.contains((8.0, 8.0), 10.0, 10.0));
+
+            // only the top left pixel is land, and row 0 is the largest y
+            let image = BoundaryMask::Image {
+                width: 2,
+                height: 2,
+                pixels: vec![200, 0, 100, 0],
+                threshold: 128,
+            };
+            assert!(image.contains((2.0, 9.0), 10.0, 10.0));
+            assert!(!image.contains((2.0, 2.0), 10.0, 10.0));
+            assert!(!image.contains((8.0, 9.0), 10.0, 10.0));
+            assert!(!image.contains((10.0, 0.0), 10.0, 10.0));
+        }
+    }
+}
@@ -0,0 +1,336 @@
+pub mod save {
+    use std::{fs, path::Path};
+
+    use crate::{
+        error::error::MapGenError,
+        graph2::{
+            graph2::{Biome, Cell, CellId, Corner, CornerId, Edge, EdgeId, Graph},
+            mask::mask::BoundaryMask,
+            spatial::spatial::SpatialIndex,
+        },
+    };
+
+    const MAGIC: &[u8; 4] = b"VMAP";
+    /// Bumped whenever the layout below changes, older files are rejected
+    pub const FORMAT_VERSION: u16 = 1;
+
+    fn invalid(reason: &str) -> MapGenError {
+        return MapGenError::InvalidMapFile(String::from(reason));
+    }
+
+    // all values are little endian, lists are prefixed with a u32 length
+    struct Writer {
+        bytes: Vec<u8>,
+    }
+
+    impl Writer {
+        fn u8(&mut self, value: u8) {
+            self.bytes.push(value);
+        }
+        fn u16(&mut self, value: u16) {
+            self.bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        fn u32(&mut self, value: u32) {
+            self.bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        fn f32(&mut self, value: f32) {
+            self.bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        fn f64(&mut self, value: f64) {
+            self.bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        fn len(&mut self, len: usize) {
+            self.u32(len as u32);
+        }
+        fn pos(&mut self, pos: (f32, f32)) {
+            self.f32(pos.0);
+            self.f32(pos.1);
+        }
+    }
+
+    struct Reader<'a> {
+        bytes: &'a [u8],
+        offset: usize,
+    }
+
+    impl<'a> Reader<'a> {
+        fn take<const N: usize>(&mut self) -> Result<[u8; N], MapGenError> {
+            let end = self.offset + N;
+            let Some(slice) = self.bytes.get(self.offset..end) else {
+                return Err(invalid("unexpected end of file"));
+            };
+            self.offset = end;
+            return Ok(slice.try_into().unwrap());
+        }
+        fn u8(&mut self) -> Result<u8, MapGenError> {
+            return Ok(self.take::<1>()?[0]);
+        }
+        fn u16(&mut self) -> Result<u16, MapGenError> {
+            return Ok(u16::from_le_bytes(self.take()?));
+        }
+        fn u32(&mut self) -> Result<u32, MapGenError> {
+            return Ok(u32::from_le_bytes(self.take()?));
+        }
+        fn f32(&mut self) -> Result<f32, MapGenError> {
+            return Ok(f32::from_le_bytes(self.take()?));
+        }
+        fn f64(&mut self) -> Result<f64, MapGenError> {
+            return Ok(f64::from_le_bytes(self.take()?));
+        }
+        // a length can't claim more items than there are bytes left, which
+        // stops a corrupt count from reserving gigabytes up front
+        fn len(&mut self, item_size: usize) -> Result<usize, MapGenError> {
+            let len = self.u32()? as usize;
+            if len * item_size > self.bytes.len() - self.offset {
+                return Err(invalid("list length runs past the end of the file"));
+            }
+            return Ok(len);
+        }
+        fn pos(&mut self) -> Result<(f32, f32), MapGenError> {
+            return Ok((self.f32()?, self.f32()?));
+        }
+        fn list<T>(
+            &mut self,
+            item_size: usize,
+            mut read: impl FnMut(&mut Self) -> Result<T, MapGenError>,
+        ) -> Result<Vec<T>, MapGenError> {
+            let len = self.len(item_size)?;
+            let mut output: Vec<T> = Vec::with_capacity(len);
+            for _i in 0..len {
+                output.push(read(self)?);
+            }
+            return Ok(output);
+        }
+    }
+
+    fn write_boundary(writer: &mut Writer, boundary: &BoundaryMask) {
+        match boundary {
+            BoundaryMask::Rectangle => writer.u8(0),
+            BoundaryMask::Polygon(points) => {
+                writer.u8(1);
+                writer.len(points.len());
+                for (x, y) in points {
+                    writer.f64(*x);
+                    writer.f64(*y);
+                }
+            }
+            BoundaryMask::Image {
+                width,
+                height,
+                pixels,
+                threshold,
+            } => {
+                writer.u8(2);
+                writer.u32(*width as u32);
+                writer.u32(*height as u32);
+                writer.u8(*threshold);
+                writer.len(pixels.len());
+                writer.bytes.extend_from_slice(pixels);
+            }
+        }
+    }
+
+    fn read_boundary(reader: &mut Reader) -> Result<BoundaryMask, MapGenError> {
+        return match reader.u8()? {
+            0 => Ok(BoundaryMask::Rectangle),
+            1 => Ok(BoundaryMask::Polygon(
+                reader.list(16, |r| Ok((r.f64()?, r.f64()?)))?,
+            )),
+            2 => {
+                let width = reader.u32()? as usize;
+                let height = reader.u32()? as usize;
+                let threshold = reader.u8()?;
+                let pixels = reader.list(1, |r| r.u8())?;
+                if pixels.len() != width * height {
+                    return Err(invalid("boundary image size does not match its pixels"));
+                }
+                Ok(BoundaryMask::Image {
+                    width,
+                    height,
+                    pixels,
+                    threshold,
+                })
+            }
+            _ => Err(invalid("unknown boundary mask type")),
+        };
+    }
+
+    impl Graph {
+        /// Encodes the graph and all of its terrain data into the versioned
+        /// binary map format read by `from_bytes`.
+        pub fn to_bytes(&self) -> Vec<u8> {
+            let mut writer = Writer { bytes: Vec::new() };
+            writer.bytes.extend_from_slice(MAGIC);
+            writer.u16(FORMAT_VERSION);
+            writer.f64(self.x_scale);
+            writer.f64(self.y_scale);
+            writer.u8(self.wrap_x as u8 | (self.sphere as u8) << 1);
+            write_boundary(&mut writer, &self.boundary);
+
+            writer.len(self.corners.len());
+            for corner in &self.corners {
+                writer.pos(corner.pos);
+                writer.f32(corner.elevation);
+                writer.len(corner.edges.len());
+                corner.edges.iter().for_each(|id| writer.u32(id.0));
+            }
+            writer.len(self.edges.len());
+            for edge in &self.edges {
+                writer.u32(edge.corners.0 .0);
+                writer.u32(edge.corners.1 .0);
+                writer.f32(edge.river);
+                writer.len(edge.corner_midpoints.len());
+                edge.corner_midpoints.iter().for_each(|p| writer.pos(*p));
+                writer.len(edge.cells.len());
+                edge.cells.iter().for_each(|id| writer.u32(id.0));
+            }
+            writer.len(self.cells.len());
+            for cell in &self.cells {
+                writer.u8(cell.water as u8 | (cell.ocean as u8) << 1 | (cell.coast as u8) << 2);
+                writer.f32(cell.moisture);
+                let biome = Biome::ALL.iter().position(|b| b.eq(&cell.biome)).unwrap();
+                writer.u8(biome as u8);
+                writer.len(cell.edges.len());
+                cell.edges.iter().for_each(|id| writer.u32(id.0));
+            }
+            return writer.bytes;
+        }
+
+        /// Decodes a graph written by `to_bytes`. Rejects files from another
+        /// format version, truncated or trailing data, and graphs that fail
+        /// `validate`, so a loaded graph is always safe to index.
+        pub fn from_bytes(bytes: &[u8]) -> Result<Graph, MapGenError> {
+            let mut reader = Reader { bytes, offset: 0 };
+            if &reader.take::<4>()? != MAGIC {
+                return Err(invalid("not a map file"));
+            }
+            let version = reader.u16()?;
+            if version != FORMAT_VERSION {
+                return Err(MapGenError::InvalidMapFile(format!(
+                    "unsupported format version {}, expected {}",
+                    version, FORMAT_VERSION
+                )));
+            }
+            let x_scale = reader.f64()?;
+            let y_scale = reader.f64()?;
+            let flags = reader.u8()?;
+            let boundary = read_boundary(&mut reader)?;
+
+            let corners = reader.list(16, |r| {
+                return Ok(Corner {
+                    pos: r.pos()?,
+                    elevation: r.f32()?,
+                    edges: r.list(4, |r| Ok(EdgeId(r.u32()?)))?,
+                });
+            })?;
+            let edges = reader.list(20, |r| {
+                return Ok(Edge {
+                    corners: (CornerId(r.u32()?), CornerId(r.u32()?)),
+                    river: r.f32()?,
+                    corner_midpoints: r.list(8, |r| r.pos())?,
+                    cells: r.list(4, |r| Ok(CellId(r.u32()?)))?,
+                });
+            })?;
+            let cells = reader.list(10, |r| {
+                let flags = r.u8()?;
+                let moisture = r.f32()?;
+                let Some(biome) = Biome::ALL.get(r.u8()? as usize) else {
+                    return Err(invalid("unknown biome"));
+                };
+                return Ok(Cell {
+                    water: flags & 1 != 0,
+                    ocean: flags & 2 != 0,
+                    coast: flags & 4 != 0,
+                    moisture,
+                    biome: biome.clone(),
+                    edges: r.list(4, |r| Ok(EdgeId(r.u32()?)))?,
+                });
+            })?;
+            if reader.offset != bytes.len() {
+                return Err(invalid("trailing data after the graph"));
+            }
+
+            let mut graph = Graph {
+                corners,
+                edges,
+                cells,
+                x_scale,
+                y_scale,
+                wrap_x: flags & 1 != 0,
+                sphere: flags & 2 != 0,
+                boundary,
+                spatial_index: SpatialIndex::default(),
+            };
+            let violations = graph.validate();
+            if !violations.is_empty() {
+                return Err(MapGenError::InvalidGraph(violations));
+            }
+            graph.rebuild_spatial_index();
+            return Ok(graph);
+        }
+
+        pub fn save(&self, path: impl AsRef<Path>) -> Result<(), MapGenError> {
+            return fs::write(path, self.to_bytes()).map_err(|e| MapGenError::Io(e.to_string()));
+        }
+
+        pub fn load(path: impl AsRef<Path>) -> Result<Graph, MapGenError> {
+            let bytes = fs::read(path).map_err(|e| MapGenError::Io(e.to_string()))?;
+            return Graph::from_bytes(&bytes);
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::{config::config::GenerationConfig, terrain2::terrain2::full_terrain_gen};
+
+        fn test_graph() -> Graph {
+            let config = GenerationConfig {
+                cell_count: 200,
+                ..Default::default()
+            };
+            return full_terrain_gen(&config, 9).unwrap().0;
+        }
+
+        #[test]
+        fn save_load_round_trip_test() {
+            let graph = test_graph();
+            assert_eq!(Graph::from_bytes(&graph.to_bytes()).unwrap(), graph);
+
+            let path = std::env::temp_dir().join("voronoi_map_gen_round_trip.vmap");
+            graph.save(&path).unwrap();
+            let loaded = Graph::load(&path);
+            fs::remove_file(&path).unwrap();
+            assert_eq!(loaded.unwrap(), graph);
+        }
+
+        #[test]
+        fn load_rejects_bad_files_test() {
+            let bytes = test_graph().to_bytes();
+            let is_invalid_file = |bytes: &[u8]| {
+                matches!(
+                    Graph::from_bytes(bytes),
+                    Err(MapGenError::InvalidMapFile(_))
+                )
+            };
+
+            assert!(is_invalid_file(b"PNG\0 not a map"));
+            let mut future_version = bytes.clone();
+            future_version[4] = 2;
+            assert!(is_invalid_file(&future_version));
+            for len in (0..bytes.len()).step_by(97) {
+                assert!(is_invalid_file(&bytes[..len]));
+            }
+            let mut trailing = bytes.clone();
+            trailing.push(0);
+            assert!(is_invalid_file(&trailing));
+
+            let mut dangling = test_graph();
+            dangling.cells[0].edges[0] = EdgeId(u32::MAX);
+            assert!(matches!(
+                Graph::from_bytes(&dangling.to_bytes()),
+                Err(MapGenError::InvalidGraph(_))
+            ));
+        }
+    }
+}
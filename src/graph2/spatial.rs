@@ -0,0 +1,396 @@
+pub mod spatial {
+    use crate::graph2::graph2::{CellId, CornerId, EdgeId, Graph};
+
+    /// A uniform grid of square buckets over the graph bounds. Each bucket lists
+    /// the cells, corners and edges whose bounding boxes overlap it, so point
+    /// queries only test the handful of features near the query position.
+    #[derive(Debug, Clone, PartialEq, Default)]
+    pub struct SpatialIndex {
+        columns: usize,
+        rows: usize,
+        bucket_width: f32,
+        bucket_height: f32,
+        wrap_x: bool,
+        sphere: bool,
+        cells: Vec<Vec<CellId>>,
+        corners: Vec<Vec<CornerId>>,
+        edges: Vec<Vec<EdgeId>>,
+    }
+
+    fn distance(a: (f32, f32), b: (f32, f32)) -> f32 {
+        return ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt();
+    }
+
+    fn segment_distance(pos: (f32, f32), start: (f32, f32), end: (f32, f32)) -> f32 {
+        let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+        let length_sq = dx * dx + dy * dy;
+        if length_sq == 0.0 {
+            return distance(pos, start);
+        }
+        let t = (((pos.0 - start.0) * dx + (pos.1 - start.1) * dy) / length_sq).clamp(0.0, 1.0);
+        return distance(pos, (start.0 + t * dx, start.1 + t * dy));
+    }
+
+    // even-odd ray cast along +x
+    pub(crate) fn polygon_contains(points: &[(f32, f32)], pos: (f32, f32)) -> bool {
+        let mut inside = false;
+        for (i, a) in points.iter().enumerate() {
+            let b = points[(i + 1) % points.len()];
+            if (a.1 > pos.1) != (b.1 > pos.1) {
+                let crossing_x = a.0 + (pos.1 - a.1) * (b.0 - a.0) / (b.1 - a.1);
+                if pos.0 < crossing_x {
+                    inside = !inside;
+                }
+            }
+        }
+        return inside;
+    }
+
+    impl SpatialIndex {
+        pub fn build(graph: &Graph) -> SpatialIndex {
+            let feature_count = graph.cells.len().max(1) as f64;
+            let target_size = ((graph.x_scale * graph.y_scale) / feature_count).sqrt();
+            let columns = ((graph.x_scale / target_size).ceil() as usize).max(1);
+            let rows = ((graph.y_scale / target_size).ceil() as usize).max(1);
+            // buckets tile the map exactly, so a column index wraps cleanly at the seam
+            let mut index = SpatialIndex {
+                columns,
+                rows,
+                bucket_width: (graph.x_scale / columns as f64) as f32,
+                bucket_height: (graph.y_scale / rows as f64) as f32,
+                wrap_x: graph.wrap_x,
+                sphere: graph.sphere,
+                cells: vec![Vec::new(); columns * rows],
+                corners: vec![Vec::new(); columns * rows],
+                edges: vec![Vec::new(); columns * rows],
+            };
+
+            for cell_id in graph.cell_ids() {
+                let positions = match graph.get_cell_outline(cell_id) {
+                    Ok(outline) => outline,
+                    Err(_) => graph
+                        .get_cell_corners(cell_id)
+                        .iter()
+                        .map(|c| c.pos)
+                        .collect(),
+                };
+                if positions.is_empty() {
+                    continue;
+                }
+                for bucket in index.buckets_covering(&positions) {
+                    index.cells[bucket].push(cell_id);
+                }
+            }
+            for corner_id in graph.corner_ids() {
+                let bucket = index.bucket_at(graph[corner_id].pos);
+                index.corners[bucket].push(corner_id);
+            }
+            for edge_id in graph.edge_ids() {
+                let (c_1, c_2) = graph[edge_id].corners;
+                let start = graph[c_1].pos;
+                let end = graph.unwrap_pos(start, graph[c_2].pos);
+                let mut positions = vec![start, end];
+                if graph.sphere {
+                    let (min_y, max_y) = graph.sphere_arc_y_range(start, end);
+                    positions.extend([(start.0, min_y), (start.0, max_y)]);
+                }
+                for bucket in index.buckets_covering(&positions) {
+                    index.edges[bucket].push(edge_id);
+                }
+            }
+            return index;
+        }
+
+        // columns past either side of the map wrap around on wrapping maps,
+        // and are clamped to the edge column otherwise
+        fn column_index(&self, column: isize) -> Option<usize> {
+            if self.wrap_x {
+                return Some(column.rem_euclid(self.columns as isize) as usize);
+            }
+            if column < 0 || column >= self.columns as isize {
+                return None;
+            }
+            return Some(column as usize);
+        }
+
+        fn bucket_coords(&self, pos: (f32, f32)) -> (isize, isize) {
+            let column = (pos.0 / self.bucket_width).floor() as isize;
+            let row = (pos.1 / self.bucket_height).floor() as isize;
+            if self.wrap_x {
+                return (column, row.clamp(0, self.rows as isize - 1));
+            }
+            return (
+                column.clamp(0, self.columns as isize - 1),
+                row.clamp(0, self.rows as isize - 1),
+            );
+        }
+
+        fn bucket_at(&self, pos: (f32, f32)) -> usize {
+            let (column, row) = self.bucket_coords(pos);
+            return row as usize * self.columns + self.column_index(column).unwrap_or(0);
+        }
+
+        fn buckets_covering(&self, positions: &[(f32, f32)]) -> Vec<usize> {
+            let min = positions.iter().fold((f32::MAX, f32::MAX), |acc, p| {
+                return (acc.0.min(p.0), acc.1.min(p.1));
+            });
+            let max = positions.iter().fold((f32::MIN, f32::MIN), |acc, p| {
+                return (acc.0.max(p.0), acc.1.max(p.1));
+            });
+            let (min_column, min_row) = self.bucket_coords(min);
+            let (max_column, max_row) = self.bucket_coords(max);
+            let mut output: Vec<usize> = Vec::new();
+            for row in min_row..=max_row {
+                for column in min_column..=max_column {
+                    if let Some(column) = self.column_index(column) {
+                        output.push(row as usize * self.columns + column);
+                    }
+                }
+            }
+            return output;
+        }
+
+        // searches outwards in square rings of buckets. Anything outside ring
+        // r is at least r buckets away, so the search stops once the best
+        // match found is closer than that
+        fn nearest<T: Copy>(
+            &self,
+            buckets: &[Vec<T>],
+            pos: (f32, f32),
+            distance_to: impl Fn(T) -> f32,
+        ) -> Option<T> {
+            if buckets.is_empty() {
+                return None;
+            }
+            if self.sphere {
+                return self.nearest_by_rows(buckets, pos, distance_to);
+            }
+            let (column, row) = self.bucket_coords(pos);
+            let bucket_size = self.bucket_width.min(self.bucket_height);
+            let mut best: Option<(T, f32)> = None;
+            for ring in 0..=(self.columns.max(self.rows) as isize) {
+                for y in (row - ring)..=(row + ring) {
+                    for x in (column - ring)..=(column + ring) {
+                        let on_ring = (x - column).abs() == ring || (y - row).abs() == ring;
+                        if !on_ring || y < 0 || y >= self.rows as isize {
+                            continue;
+                        }
+                        let Some(x) = self.column_index(x) else {
+                            continue;
+                        };
+                        for item in &buckets[y as usize * self.columns + x] {
+                            let d = distance_to(*item);
+                            if best.is_none_or(|(_item, best_d)| d < best_d) {
+                                best = Some((*item, d));
+                            }
+                        }
+                    }
+                }
+                if let Some((_item, best_d)) = best {
+                    if best_d <= ring as f32 * bucket_size {
+                        break;
+                    }
+                }
+            }
+            return best.map(|(item, _d)| item);
+        }
+
+        // On a sphere buckets narrow towards the poles, so a column gives no
+        // bound on distance. Whole rows are searched outwards instead, rows
+        // more than r away are at least r bucket heights of latitude away
+        fn nearest_by_rows<T: Copy>(
+            &self,
+            buckets: &[Vec<T>],
+            pos: (f32, f32),
+            distance_to: impl Fn(T) -> f32,
+        ) -> Option<T> {
+            let (_column, row) = self.bucket_coords(pos);
+            // the map spans half a circumference from pole to pole
+            let row_distance = self.columns as f32 * self.bucket_width / (2.0 * self.rows as f32);
+            let mut best: Option<(T, f32)> = None;
+            for band in 0..(self.rows as isize) {
+                let band_rows = if band == 0 {
+                    vec![row]
+                } else {
+                    vec![row - band, row + band]
+                };
+                for y in band_rows {
+                    if y < 0 || y >= self.rows as isize {
+                        continue;
+                    }
+                    let start = y as usize * self.columns;
+                    for item in buckets[start..start + self.columns].iter().flatten() {
+                        let d = distance_to(*item);
+                        if best.is_none_or(|(_item, best_d)| d < best_d) {
+                            best = Some((*item, d));
+                        }
+                    }
+                }
+                if let Some((_item, best_d)) = best {
+                    if best_d <= band as f32 * row_distance {
+                        break;
+                    }
+                }
+            }
+            return best.map(|(item, _d)| item);
+        }
+    }
+
+    impl Graph {
+        /// Rebuilds the spatial index. Only needed after moving corners or
+        /// building a `Graph` by hand, `generate_base_graph` builds it once.
+        pub fn rebuild_spatial_index(&mut self) {
+            self.spatial_index = SpatialIndex::build(self);
+        }
+
+        /// The cell containing `pos`, or `None` outside the map bounds. On
+        /// wrapping maps `pos` is first folded back across the seam.
+        pub fn cell_at(&self, pos: (f32, f32)) -> Option<CellId> {
+            let index = &self.spatial_index;
+            let pos = self.wrap_pos(pos);
+            if index.cells.is_empty()
+                || !(0.0..=self.x_scale as f32).contains(&pos.0)
+                || !(0.0..=self.y_scale as f32).contains(&pos.1)
+            {
+                return None;
+            }
+            let candidates = &index.cells[index.bucket_at(pos)];
+            if self.sphere {
+                return self.sphere_cell_at(candidates, pos);
+            }
+            return candidates.iter().copied().find(|cell_id| {
+                match self.get_cell_outline(*cell_id) {
+                    Ok(outline) => {
+                        // the outline may sit a map width to either side of pos
+                        let width = self.x_scale as f32;
+                        let shifts: &[f32] = if self.wrap_x {
+                            &[0.0, -width, width]
+                        } else {
+                            &[0.0]
+                        };
+                        shifts
+                            .iter()
+                            .any(|shift| polygon_contains(&outline, (pos.0 + shift, pos.1)))
+                    }
+                    Err(_) => false,
+                }
+            });
+        }
+
+        pub fn nearest_corner(&self, pos: (f32, f32)) -> Option<CornerId> {
+            let index = &self.spatial_index;
+            let pos = self.wrap_pos(pos);
+            return index.nearest(&index.corners, pos, |id| {
+                self.pos_distance(pos, self[id].pos)
+            });
+        }
+
+        /// The edge whose straight segment between its corners passes closest to
+        /// `pos`. On spherical maps edges are great circle arcs instead
+        pub fn nearest_edge(&self, pos: (f32, f32)) -> Option<EdgeId> {
+            let index = &self.spatial_index;
+            let pos = self.wrap_pos(pos);
+            return index.nearest(&index.edges, pos, |id| {
+                let (c_1, c_2) = self[id].corners;
+                if self.sphere {
+                    return self.sphere_segment_distance(pos, self[c_1].pos, self[c_2].pos);
+                }
+                let start = self.unwrap_pos(pos, self[c_1].pos);
+                let end = self.unwrap_pos(start, self[c_2].pos);
+                return segment_distance(pos, start, end);
+            });
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        use super::*;
+        use crate::{
+            config::config::GenerationConfig,
+            graph2::{graph2::generate_base_graph, sphere::sphere::generate_sphere_graph},
+        };
+
+        fn test_graph(wrap_x: bool) -> Graph {
+            let config = GenerationConfig {
+                cell_count: 500,
+                x_scale: 400.0,
+                y_scale: 200.0,
+                wrap_x,
+                ..Default::default()
+            };
+            return generate_base_graph(&config, &mut StdRng::seed_from_u64(6)).unwrap();
+        }
+
+        #[test]
+        fn cell_at_finds_cell_centres_test() {
+            for wrap_x in [false, true] {
+                let graph = test_graph(wrap_x);
+                for cell_id in graph.cell_ids() {
+                    assert_eq!(graph.cell_at(graph.get_cell_center(cell_id)), Some(cell_id));
+                }
+                assert_eq!(graph.cell_at((10.0, 201.0)), None);
+            }
+            assert_eq!(test_graph(false).cell_at((-1.0, 10.0)), None);
+            assert!(test_graph(true).cell_at((-1.0, 10.0)).is_some());
+        }
+
+        #[test]
+        fn nearest_queries_match_linear_scan_test() {
+            let sphere = GenerationConfig {
+                cell_count: 500,
+                x_scale: 400.0,
+                y_scale: 200.0,
+                sphere: true,
+                ..Default::default()
+            };
+            let graphs = [
+                test_graph(false),
+                test_graph(true),
+                generate_sphere_graph(&sphere, &mut StdRng::seed_from_u64(6)).unwrap(),
+            ];
+            for graph in graphs {
+                let mut rng = StdRng::seed_from_u64(7);
+                let mut queries: Vec<(f32, f32)> = (0..200)
+                    .map(|_i| (rng.gen::<f32>() * 400.0, rng.gen::<f32>() * 200.0))
+                    .collect();
+                // next to the poles and either side of the antimeridian
+                for x in (0..=80).map(|i| i as f32 * 5.0) {
+                    for y in [0.0, 0.5, 4.0, 10.0, 100.0, 190.0, 196.0, 199.5, 200.0] {
+                        queries.push((x, y));
+                    }
+                }
+                for pos in queries {
+                    let corner_id = graph.nearest_corner(pos).unwrap();
+                    let closest_corner = graph
+                        .corners
+                        .iter()
+                        .map(|c| graph.pos_distance(pos, c.pos))
+                        .fold(f32::MAX, f32::min);
+                    assert_eq!(
+                        graph.pos_distance(pos, graph[corner_id].pos),
+                        closest_corner
+                    );
+
+                    let edge_distance = |id: EdgeId| {
+                        let (c_1, c_2) = graph[id].corners;
+                        if graph.sphere {
+                            return graph.sphere_segment_distance(
+                                pos,
+                                graph[c_1].pos,
+                                graph[c_2].pos,
+                            );
+                        }
+                        let start = graph.unwrap_pos(pos, graph[c_1].pos);
+                        let end = graph.unwrap_pos(start, graph[c_2].pos);
+                        return segment_distance(pos, start, end);
+                    };
+                    let edge_id = graph.nearest_edge(pos).unwrap();
+                    let closest_edge = graph.edge_ids().map(edge_distance).fold(f32::MAX, f32::min);
+                    assert_eq!(edge_distance(edge_id), closest_edge);
+                }
+            }
+        }
+    }
+}
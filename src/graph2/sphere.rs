@@ -0,0 +1,548 @@
+pub mod sphere {
+    use std::cmp::Ordering;
+    use std::collections::BTreeMap;
+    use std::f64::consts::{FRAC_PI_2, PI, TAU};
+
+    use rand::Rng;
+    use voronator::delaunator::{triangulate_from_tuple, Point};
+
+    use crate::{
+        config::config::GenerationConfig,
+        error::error::MapGenError,
+        graph2::{
+            graph2::{
+                link_back_references, Biome, Cell, CellId, Corner, CornerId, Edge, EdgeId, Graph,
+            },
+            spatial::spatial::SpatialIndex,
+        },
+        voronoi::voronoi::RelaxationReport,
+    };
+
+    type Vec3 = [f64; 3];
+
+    fn sub(a: Vec3, b: Vec3) -> Vec3 {
+        return [a[0] - b[0], a[1] - b[1], a[2] - b[2]];
+    }
+
+    fn dot(a: Vec3, b: Vec3) -> f64 {
+        return a[0] * b[0] + a[1] * b[1] + a[2] * b[2];
+    }
+
+    fn cross(a: Vec3, b: Vec3) -> Vec3 {
+        return [
+            a[1] * b[2] - a[2] * b[1],
+            a[2] * b[0] - a[0] * b[2],
+            a[0] * b[1] - a[1] * b[0],
+        ];
+    }
+
+    fn length(a: Vec3) -> f64 {
+        return dot(a, a).sqrt();
+    }
+
+    fn normalise(a: Vec3) -> Vec3 {
+        let l = length(a);
+        return [a[0] / l, a[1] / l, a[2] / l];
+    }
+
+    // whether v, on the great circle through a and b, lies on the shorter arc
+    // between them
+    fn on_arc(a: Vec3, b: Vec3, normal: Vec3, v: Vec3) -> bool {
+        return dot(cross(a, v), normal) >= 0.0 && dot(cross(v, b), normal) >= 0.0;
+    }
+
+    fn random_unit_vector(rng: &mut impl Rng) -> Vec3 {
+        // uniform in z and longitude is uniform over the sphere's area
+        let z = rng.gen::<f64>() * 2.0 - 1.0;
+        let angle = rng.gen::<f64>() * TAU;
+        let r = (1.0 - z * z).sqrt();
+        return [r * angle.cos(), r * angle.sin(), z];
+    }
+
+    // equirectangular projection, longitude -180..180 across x and latitude
+    // -90..90 up y
+    fn project(v: Vec3, x_scale: f64, y_scale: f64) -> (f32, f32) {
+        let longitude = v[1].atan2(v[0]);
+        let latitude = v[2].clamp(-1.0, 1.0).asin();
+        let x = ((longitude + PI) / TAU * x_scale).rem_euclid(x_scale);
+        let y = (latitude + FRAC_PI_2) / PI * y_scale;
+        return (if x >= x_scale { 0.0 } else { x } as f32, y as f32);
+    }
+
+    fn unproject(pos: (f32, f32), x_scale: f64, y_scale: f64) -> Vec3 {
+        let longitude = pos.0 as f64 / x_scale * TAU - PI;
+        let latitude = pos.1 as f64 / y_scale * PI - FRAC_PI_2;
+        return [
+            latitude.cos() * longitude.cos(),
+            latitude.cos() * longitude.sin(),
+            latitude.sin(),
+        ];
+    }
+
+    // the voronoi diagram of points on the unit sphere, built from its dual
+    // delaunay triangulation
+    struct SphericalDiagram {
+        sites: Vec<Vec3>,
+        // circumcentre of each delaunay triangle, these become the corners
+        centers: Vec<Vec3>,
+        // triangles around each site, ordered by angle around it
+        site_triangles: Vec<Vec<usize>>,
+        // each pair of neighbouring sites, and the two triangles either side
+        // of the edge between them
+        site_edges: BTreeMap<(usize, usize), (usize, usize)>,
+    }
+
+    // Delaunay on a sphere is the convex hull of the sites. Projecting
+    // stereographically from one site keeps circles as circles, so a planar
+    // delaunay of the rest gives every triangle except those touching the
+    // pivot, and those are fanned from the pivot to the planar hull.
+    fn spherical_voronoi(sites: Vec<Vec3>) -> Result<SphericalDiagram, MapGenError> {
+        let degenerate = || {
+            return MapGenError::DegeneratePoints(format!(
+                "no spherical triangulation for {} points, they may be duplicated",
+                sites.len()
+            ));
+        };
+        let pivot = sites.len() - 1;
+        let p = sites[pivot];
+        let axis = if p[0].abs() < 0.9 {
+            [1.0, 0.0, 0.0]
+        } else {
+            [0.0, 1.0, 0.0]
+        };
+        let u = normalise(cross(axis, p));
+        let v = cross(p, u);
+        let mut planar: Vec<(f64, f64)> = Vec::with_capacity(pivot);
+        for site in &sites[..pivot] {
+            let denominator = 1.0 - dot(*site, p);
+            if denominator < 1e-12 {
+                return Err(degenerate());
+            }
+            planar.push((dot(*site, u) / denominator, dot(*site, v) / denominator));
+        }
+        let (delaunay, _points) =
+            triangulate_from_tuple::<Point>(&planar).ok_or_else(degenerate)?;
+
+        let mut triangles: Vec<[usize; 3]> = delaunay
+            .triangles
+            .chunks(3)
+            .map(|t| [t[0], t[1], t[2]])
+            .collect();
+        for (i, a) in delaunay.hull.iter().enumerate() {
+            let b = delaunay.hull[(i + 1) % delaunay.hull.len()];
+            triangles.push([*a, b, pivot]);
+        }
+
+        let centers: Vec<Vec3> = triangles
+            .iter()
+            .map(|[a, b, c]| {
+                let normal = normalise(cross(sub(sites[*b], sites[*a]), sub(sites[*c], sites[*a])));
+                if dot(normal, sites[*a]) < 0.0 {
+                    return [-normal[0], -normal[1], -normal[2]];
+                }
+                return normal;
+            })
+            .collect();
+
+        let mut site_triangles: Vec<Vec<usize>> = vec![Vec::new(); sites.len()];
+        let mut edge_triangles: BTreeMap<(usize, usize), Vec<usize>> = BTreeMap::new();
+        for (t, corners) in triangles.iter().enumerate() {
+            for i in 0..3 {
+                let (a, b) = (corners[i], corners[(i + 1) % 3]);
+                site_triangles[a].push(t);
+                edge_triangles
+                    .entry((a.min(b), a.max(b)))
+                    .or_default()
+                    .push(t);
+            }
+        }
+        let mut site_edges: BTreeMap<(usize, usize), (usize, usize)> = BTreeMap::new();
+        for (key, adjacent) in edge_triangles {
+            let [t_1, t_2] = adjacent[..] else {
+                return Err(degenerate());
+            };
+            site_edges.insert(key, (t_1, t_2));
+        }
+
+        for (s, around) in site_triangles.iter_mut().enumerate() {
+            let site = sites[s];
+            let e_1 = normalise(cross(
+                if site[0].abs() < 0.9 {
+                    [1.0, 0.0, 0.0]
+                } else {
+                    [0.0, 1.0, 0.0]
+                },
+                site,
+            ));
+            let e_2 = cross(site, e_1);
+            let angle = |t: &usize| dot(centers[*t], e_2).atan2(dot(centers[*t], e_1));
+            around.sort_by(|a, b| angle(a).total_cmp(&angle(b)));
+        }
+
+        return Ok(SphericalDiagram {
+            sites,
+            centers,
+            site_triangles,
+            site_edges,
+        });
+    }
+
+    // fans each cell from its site, giving its approximate area and area
+    // weighted centroid pushed back out onto the sphere
+    fn cell_area_centroid(diagram: &SphericalDiagram, s: usize) -> (f64, Vec3) {
+        let site = diagram.sites[s];
+        let around = &diagram.site_triangles[s];
+        let mut area = 0.0;
+        let mut centroid = [0.0, 0.0, 0.0];
+        for (i, t) in around.iter().enumerate() {
+            let a = diagram.centers[*t];
+            let b = diagram.centers[around[(i + 1) % around.len()]];
+            let weight = length(cross(sub(a, site), sub(b, site))) / 2.0;
+            area += weight;
+            for axis in 0..3 {
+                centroid[axis] += weight * (site[axis] + a[axis] + b[axis]) / 3.0;
+            }
+        }
+        if area <= 0.0 {
+            return (area, site);
+        }
+        return (area, normalise(centroid));
+    }
+
+    fn relax_sphere_n(
+        diagram: SphericalDiagram,
+        config: &GenerationConfig,
+    ) -> Result<(SphericalDiagram, RelaxationReport), MapGenError> {
+        let radius = config.x_scale / TAU;
+        let spacing = (4.0 * PI / diagram.sites.len() as f64).sqrt();
+        let mut base = diagram;
+        let mut iterations = 0;
+        let mut max_displacement = 0.0;
+        while iterations < config.relax_factor {
+            let mut displacement: f64 = 0.0;
+            let relaxed: Vec<Vec3> = (0..base.sites.len())
+                .map(|s| {
+                    let (_area, centroid) = cell_area_centroid(&base, s);
+                    displacement = displacement.max(length(sub(centroid, base.sites[s])));
+                    return centroid;
+                })
+                .collect();
+            base = spherical_voronoi(relaxed)?;
+            iterations += 1;
+            max_displacement = displacement * radius;
+            if displacement <= config.relax_tolerance * spacing {
+                break;
+            }
+        }
+        let areas: Vec<f64> = (0..base.sites.len())
+            .map(|s| cell_area_centroid(&base, s).0)
+            .collect();
+        let mean = areas.iter().sum::<f64>() / areas.len() as f64;
+        let variance = areas.iter().map(|a| (a - mean).powi(2)).sum::<f64>() / areas.len() as f64;
+        return Ok((
+            base,
+            RelaxationReport {
+                iterations,
+                max_displacement,
+                area_variance: variance / (mean * mean),
+            },
+        ));
+    }
+
+    /// Builds a planet from `config.cell_count` sites on the unit sphere. Corner
+    /// positions are stored in equirectangular projection over `x_scale` by
+    /// `y_scale`, so every terrain stage runs on it unchanged. A 2:1 ratio keeps
+    /// cells the right shape at the equator.
+    pub fn generate_sphere_graph(
+        config: &GenerationConfig,
+        rng: &mut impl Rng,
+    ) -> Result<Graph, MapGenError> {
+        let (graph, _relaxation) = build_sphere_graph(config, rng)?;
+        return Ok(graph);
+    }
+
+    /// `generate_sphere_graph`, along with how the sites were relaxed
+    pub(crate) fn build_sphere_graph(
+        config: &GenerationConfig,
+        rng: &mut impl Rng,
+    ) -> Result<(Graph, RelaxationReport), MapGenError> {
+        config.validate()?;
+        let sites: Vec<Vec3> = (0..config.cell_count)
+            .map(|_i| random_unit_vector(rng))
+            .collect();
+        let (diagram, relaxation) = relax_sphere_n(spherical_voronoi(sites)?, config)?;
+
+        let mut graph = Graph {
+            corners: diagram
+                .centers
+                .iter()
+                .map(|center| Corner {
+                    pos: project(*center, config.x_scale, config.y_scale),
+                    edges: Vec::new(),
+                    elevation: 0.0,
+                })
+                .collect(),
+            edges: Vec::new(),
+            cells: Vec::new(),
+            x_scale: config.x_scale,
+            y_scale: config.y_scale,
+            wrap_x: true,
+            sphere: true,
+            boundary: config.boundary.clone(),
+            spatial_index: SpatialIndex::default(),
+        };
+        let mut edge_lookup: BTreeMap<(usize, usize), EdgeId> = BTreeMap::new();
+        for (t_1, t_2) in diagram.site_edges.values() {
+            edge_lookup.insert(
+                (*t_1.min(t_2), *t_1.max(t_2)),
+                EdgeId(graph.edges.len() as u32),
+            );
+            graph.edges.push(Edge {
+                corners: (CornerId(*t_1 as u32), CornerId(*t_2 as u32)),
+                corner_midpoints: Vec::new(),
+                cells: Vec::new(),
+                river: 0.0,
+            });
+        }
+        for around in &diagram.site_triangles {
+            let mut cell = Cell {
+                edges: Vec::new(),
+                water: false,
+                ocean: false,
+                moisture: 0.0,
+                biome: Biome::Bare,
+                coast: false,
+            };
+            for (i, t_1) in around.iter().enumerate() {
+                let t_2 = around[(i + 1) % around.len()];
+                if let Some(edge_id) = edge_lookup.get(&(*t_1.min(&t_2), *t_1.max(&t_2))) {
+                    cell.edges.push(*edge_id);
+                }
+            }
+            graph.cells.push(cell);
+        }
+        link_back_references(&mut graph);
+        let violations = graph.validate();
+        if !violations.is_empty() {
+            return Err(MapGenError::InvalidGraph(violations));
+        }
+        graph.rebuild_spatial_index();
+        return Ok((graph, relaxation));
+    }
+
+    impl Graph {
+        /// Latitude and longitude in degrees of a projected position
+        pub fn get_lat_long(&self, pos: (f32, f32)) -> (f32, f32) {
+            let longitude = pos.0 as f64 / self.x_scale * 360.0 - 180.0;
+            let latitude = pos.1 as f64 / self.y_scale * 180.0 - 90.0;
+            return (latitude as f32, longitude as f32);
+        }
+
+        pub fn get_corner_lat_long(&self, id: CornerId) -> (f32, f32) {
+            return self.get_lat_long(self[id].pos);
+        }
+
+        /// Point on the unit sphere for a projected position
+        pub fn get_position_3d(&self, pos: (f32, f32)) -> (f32, f32, f32) {
+            let v = unproject(pos, self.x_scale, self.y_scale);
+            return (v[0] as f32, v[1] as f32, v[2] as f32);
+        }
+
+        pub fn get_corner_position_3d(&self, id: CornerId) -> (f32, f32, f32) {
+            return self.get_position_3d(self[id].pos);
+        }
+
+        /// The cell's centre on the unit sphere, averaged in 3D so cells over
+        /// the poles and the seam are handled
+        pub fn get_cell_center_3d(&self, id: CellId) -> (f32, f32, f32) {
+            let sum = self
+                .get_cell_corners(id)
+                .iter()
+                .map(|corner| unproject(corner.pos, self.x_scale, self.y_scale))
+                .fold([0.0, 0.0, 0.0], |acc, v| {
+                    return [acc[0] + v[0], acc[1] + v[1], acc[2] + v[2]];
+                });
+            let v = normalise(sum);
+            return (v[0] as f32, v[1] as f32, v[2] as f32);
+        }
+
+        // great circle distance on a sphere whose equator is x_scale long
+        pub(crate) fn sphere_distance(&self, a: (f32, f32), b: (f32, f32)) -> f32 {
+            let a = unproject(a, self.x_scale, self.y_scale);
+            let b = unproject(b, self.x_scale, self.y_scale);
+            let angle = length(cross(a, b)).atan2(dot(a, b));
+            return (angle * self.x_scale / TAU) as f32;
+        }
+
+        // great circle distance from pos to the shorter arc between start and end
+        pub(crate) fn sphere_segment_distance(
+            &self,
+            pos: (f32, f32),
+            start: (f32, f32),
+            end: (f32, f32),
+        ) -> f32 {
+            let p = unproject(pos, self.x_scale, self.y_scale);
+            let a = unproject(start, self.x_scale, self.y_scale);
+            let b = unproject(end, self.x_scale, self.y_scale);
+            let normal = cross(a, b);
+            if length(normal) > f64::EPSILON {
+                let normal = normalise(normal);
+                let along = dot(p, normal);
+                // p dropped onto the arc's great circle
+                let foot = sub(p, [normal[0] * along, normal[1] * along, normal[2] * along]);
+                if on_arc(a, b, normal, foot) {
+                    let angle = along.abs().clamp(0.0, 1.0).asin();
+                    return (angle * self.x_scale / TAU) as f32;
+                }
+            }
+            return self
+                .sphere_distance(pos, start)
+                .min(self.sphere_distance(pos, end));
+        }
+
+        // the lowest and highest y the arc between start and end reaches, an
+        // arc bows towards the nearer pole so this can reach past both ends
+        pub(crate) fn sphere_arc_y_range(&self, start: (f32, f32), end: (f32, f32)) -> (f32, f32) {
+            let a = unproject(start, self.x_scale, self.y_scale);
+            let b = unproject(end, self.x_scale, self.y_scale);
+            let (mut min_y, mut max_y) = (start.1.min(end.1), start.1.max(end.1));
+            let normal = cross(a, b);
+            if length(normal) <= f64::EPSILON {
+                return (min_y, max_y);
+            }
+            let normal = normalise(normal);
+            // the great circle's northernmost point, and its southernmost opposite it
+            let top = sub(
+                [0.0, 0.0, 1.0],
+                [
+                    normal[0] * normal[2],
+                    normal[1] * normal[2],
+                    normal[2] * normal[2],
+                ],
+            );
+            if length(top) <= f64::EPSILON {
+                return (min_y, max_y);
+            }
+            let top = normalise(top);
+            for extreme in [top, [-top[0], -top[1], -top[2]]] {
+                if on_arc(a, b, normal, extreme) {
+                    let y = project(extreme, self.x_scale, self.y_scale).1;
+                    min_y = min_y.min(y);
+                    max_y = max_y.max(y);
+                }
+            }
+            return (min_y, max_y);
+        }
+
+        // Straight edges in the projection bow away from the true great circle
+        // edges, so the cell found in 2D may be a neighbour of the right one.
+        // Cells on a sphere are convex, so the point is inside a cell when it
+        // is on the same side of every edge's great circle as the centre.
+        pub(crate) fn sphere_cell_at(
+            &self,
+            candidates: &[CellId],
+            pos: (f32, f32),
+        ) -> Option<CellId> {
+            let p = unproject(pos, self.x_scale, self.y_scale);
+            let contains = |id: CellId| {
+                let Ok(corners) = self.get_cell_corners_in_order(id) else {
+                    return false;
+                };
+                let ring: Vec<Vec3> = corners
+                    .iter()
+                    .map(|corner| unproject(corner.pos, self.x_scale, self.y_scale))
+                    .collect();
+                let (cx, cy, cz) = self.get_cell_center_3d(id);
+                let center = [cx as f64, cy as f64, cz as f64];
+                return ring.iter().enumerate().all(|(i, a)| {
+                    let normal = cross(*a, ring[(i + 1) % ring.len()]);
+                    return dot(normal, p) * dot(normal, center) >= 0.0;
+                });
+            };
+            let neighbours = candidates
+                .iter()
+                .flat_map(|id| self.get_cell_adjacent_cells(*id));
+            // cells meeting at a shared edge can both pass within rounding,
+            // the one whose centre is closest settles it
+            let distance_to = |id: CellId| {
+                let (cx, cy, cz) = self.get_cell_center_3d(id);
+                return -dot([cx as f64, cy as f64, cz as f64], p);
+            };
+            return candidates
+                .iter()
+                .copied()
+                .chain(neighbours)
+                .filter(|id| contains(*id))
+                .min_by(|a, b| {
+                    distance_to(*a)
+                        .partial_cmp(&distance_to(*b))
+                        .unwrap_or(Ordering::Equal)
+                });
+        }
+
+        pub(crate) fn project_3d(&self, v: (f32, f32, f32)) -> (f32, f32) {
+            return project(
+                [v.0 as f64, v.1 as f64, v.2 as f64],
+                self.x_scale,
+                self.y_scale,
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        use super::*;
+
+        fn test_config() -> GenerationConfig {
+            return GenerationConfig {
+                cell_count: 500,
+                x_scale: 800.0,
+                y_scale: 400.0,
+                sphere: true,
+                ..Default::default()
+            };
+        }
+
+        #[test]
+        fn sphere_graph_is_closed_test() {
+            let graph =
+                generate_sphere_graph(&test_config(), &mut StdRng::seed_from_u64(1)).unwrap();
+            assert_eq!(graph.cells.len(), 500);
+            assert!(graph.edges.iter().all(|edge| edge.cells.len() == 2));
+            // Euler's formula for a sphere
+            let euler =
+                graph.corners.len() as i64 - graph.edges.len() as i64 + graph.cells.len() as i64;
+            assert_eq!(euler, 2);
+        }
+
+        #[test]
+        fn sphere_positions_round_trip_test() {
+            let graph =
+                generate_sphere_graph(&test_config(), &mut StdRng::seed_from_u64(2)).unwrap();
+            for corner_id in graph.corner_ids() {
+                let (latitude, longitude) = graph.get_corner_lat_long(corner_id);
+                assert!((-90.0..=90.0).contains(&latitude));
+                assert!((-180.0..=180.0).contains(&longitude));
+                let v = graph.get_corner_position_3d(corner_id);
+                assert!(((v.0 * v.0 + v.1 * v.1 + v.2 * v.2) - 1.0).abs() < 1e-4);
+                let pos = graph.project_3d(v);
+                assert!(graph.pos_distance(pos, graph[corner_id].pos) < 1e-2);
+            }
+        }
+
+        #[test]
+        fn cell_at_finds_polar_cells_test() {
+            let graph =
+                generate_sphere_graph(&test_config(), &mut StdRng::seed_from_u64(3)).unwrap();
+            let north = graph.cell_at((123.0, 399.9)).unwrap();
+            let south = graph.cell_at((456.0, 0.1)).unwrap();
+            assert_eq!(graph.cell_at((700.0, 399.9)), Some(north));
+            assert_eq!(graph.cell_at((10.0, 0.1)), Some(south));
+            for cell_id in graph.cell_ids() {
+                assert_eq!(graph.cell_at(graph.get_cell_center(cell_id)), Some(cell_id));
+            }
+        }
+    }
+}
@@ -0,0 +1,263 @@
+pub mod validation {
+    use std::collections::HashMap;
+
+    use crate::graph2::graph2::{CellId, CornerId, EdgeId, Graph};
+
+    /// A single broken invariant found by `Graph::validate`.
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum GraphViolation {
+        /// An id stored on a corner, edge or cell that points outside its arena
+        DanglingReference { from: String, to: String },
+        /// A cell's edges do not form a single closed loop
+        OpenCellRing { cell: CellId },
+        /// Edges must border either one (map edge) or two cells
+        EdgeCellCount { edge: EdgeId, count: usize },
+        /// A corner lists an edge that does not list the corner, or vice versa
+        CornerEdgeMismatch { corner: CornerId, edge: EdgeId },
+        /// An edge lists a cell that does not list the edge, or vice versa
+        EdgeCellMismatch { edge: EdgeId, cell: CellId },
+        /// Two corners share the exact same position
+        DuplicateCorner {
+            corner: CornerId,
+            duplicate: CornerId,
+        },
+        /// A corner lies outside of `(0, 0)-(x_scale, y_scale)`
+        OutOfBounds { corner: CornerId, pos: (f32, f32) },
+    }
+
+    impl Graph {
+        /// Checks the structural invariants of the graph, returning every
+        /// violation found. An empty list means the graph is valid.
+        pub fn validate(&self) -> Vec<GraphViolation> {
+            let mut output = check_references(self);
+            // the remaining checks index freely, so bail out on broken ids
+            if !output.is_empty() {
+                return output;
+            }
+            output.extend(check_cell_rings(self));
+            output.extend(check_edge_cells(self));
+            output.extend(check_corner_edge_symmetry(self));
+            output.extend(check_edge_cell_symmetry(self));
+            output.extend(check_duplicate_corners(self));
+            output.extend(check_bounds(self));
+            return output;
+        }
+    }
+
+    fn check_references(graph: &Graph) -> Vec<GraphViolation> {
+        let mut output: Vec<GraphViolation> = Vec::new();
+        for corner_id in graph.corner_ids() {
+            for edge_id in &graph[corner_id].edges {
+                if edge_id.index() >= graph.edges.len() {
+                    output.push(GraphViolation::DanglingReference {
+                        from: format!("{:?}", corner_id),
+                        to: format!("{:?}", edge_id),
+                    });
+                }
+            }
+        }
+        for edge_id in graph.edge_ids() {
+            let edge = &graph[edge_id];
+            for corner_id in [edge.corners.0, edge.corners.1] {
+                if corner_id.index() >= graph.corners.len() {
+                    output.push(GraphViolation::DanglingReference {
+                        from: format!("{:?}", edge_id),
+                        to: format!("{:?}", corner_id),
+                    });
+                }
+            }
+            for cell_id in &edge.cells {
+                if cell_id.index() >= graph.cells.len() {
+                    output.push(GraphViolation::DanglingReference {
+                        from: format!("{:?}", edge_id),
+                        to: format!("{:?}", cell_id),
+                    });
+                }
+            }
+        }
+        for cell_id in graph.cell_ids() {
+            for edge_id in &graph[cell_id].edges {
+                if edge_id.index() >= graph.edges.len() {
+                    output.push(GraphViolation::DanglingReference {
+                        from: format!("{:?}", cell_id),
+                        to: format!("{:?}", edge_id),
+                    });
+                }
+            }
+        }
+        return output;
+    }
+
+    fn cell_ring_is_closed(graph: &Graph, cell_id: CellId) -> bool {
+        let edges = &graph[cell_id].edges;
+        if edges.len() < 3 {
+            return false;
+        }
+        // every corner in a closed ring is shared by exactly two of its edges
+        let mut corner_counts: HashMap<CornerId, usize> = HashMap::new();
+        for edge_id in edges {
+            let (c_1, c_2) = graph[*edge_id].corners;
+            *corner_counts.entry(c_1).or_insert(0) += 1;
+            *corner_counts.entry(c_2).or_insert(0) += 1;
+        }
+        if corner_counts.values().any(|count| *count != 2) {
+            return false;
+        }
+        // and walking the ring from any edge visits all of them
+        let mut working_edges = edges.clone();
+        let (first_corner, mut last_corner) = graph[working_edges.remove(0)].corners;
+        while let Some(index) = working_edges.iter().position(|e_id| {
+            let (c_1, c_2) = graph[*e_id].corners;
+            c_1.eq(&last_corner) || c_2.eq(&last_corner)
+        }) {
+            let (c_1, c_2) = graph[working_edges.remove(index)].corners;
+            last_corner = if c_1.eq(&last_corner) { c_2 } else { c_1 };
+        }
+        return working_edges.is_empty() && last_corner.eq(&first_corner);
+    }
+
+    fn check_cell_rings(graph: &Graph) -> Vec<GraphViolation> {
+        return graph
+            .cell_ids()
+            .filter(|cell_id| !cell_ring_is_closed(graph, *cell_id))
+            .map(|cell| GraphViolation::OpenCellRing { cell })
+            .collect();
+    }
+
+    fn check_edge_cells(graph: &Graph) -> Vec<GraphViolation> {
+        return graph
+            .edge_ids()
+            .filter(|edge_id| !(1..=2).contains(&graph[*edge_id].cells.len()))
+            .map(|edge| GraphViolation::EdgeCellCount {
+                edge,
+                count: graph[edge].cells.len(),
+            })
+            .collect();
+    }
+
+    fn check_corner_edge_symmetry(graph: &Graph) -> Vec<GraphViolation> {
+        let mut output: Vec<GraphViolation> = Vec::new();
+        for corner_id in graph.corner_ids() {
+            for edge_id in &graph[corner_id].edges {
+                let (c_1, c_2) = graph[*edge_id].corners;
+                if !c_1.eq(&corner_id) && !c_2.eq(&corner_id) {
+                    output.push(GraphViolation::CornerEdgeMismatch {
+                        corner: corner_id,
+                        edge: *edge_id,
+                    });
+                }
+            }
+        }
+        for edge_id in graph.edge_ids() {
+            let (c_1, c_2) = graph[edge_id].corners;
+            for corner_id in [c_1, c_2] {
+                if !graph[corner_id].edges.contains(&edge_id) {
+                    output.push(GraphViolation::CornerEdgeMismatch {
+                        corner: corner_id,
+                        edge: edge_id,
+                    });
+                }
+            }
+        }
+        return output;
+    }
+
+    fn check_edge_cell_symmetry(graph: &Graph) -> Vec<GraphViolation> {
+        let mut output: Vec<GraphViolation> = Vec::new();
+        for edge_id in graph.edge_ids() {
+            for cell_id in &graph[edge_id].cells {
+                if !graph[*cell_id].edges.contains(&edge_id) {
+                    output.push(GraphViolation::EdgeCellMismatch {
+                        edge: edge_id,
+                        cell: *cell_id,
+                    });
+                }
+            }
+        }
+        for cell_id in graph.cell_ids() {
+            for edge_id in &graph[cell_id].edges {
+                if !graph[*edge_id].cells.contains(&cell_id) {
+                    output.push(GraphViolation::EdgeCellMismatch {
+                        edge: *edge_id,
+                        cell: cell_id,
+                    });
+                }
+            }
+        }
+        return output;
+    }
+
+    fn check_duplicate_corners(graph: &Graph) -> Vec<GraphViolation> {
+        let mut output: Vec<GraphViolation> = Vec::new();
+        let mut positions: HashMap<(u32, u32), CornerId> = HashMap::new();
+        for corner_id in graph.corner_ids() {
+            let (x, y) = graph[corner_id].pos;
+            // normalise -0.0 so it matches 0.0
+            let key = ((x + 0.0).to_bits(), (y + 0.0).to_bits());
+            if let Some(existing) = positions.get(&key) {
+                output.push(GraphViolation::DuplicateCorner {
+                    corner: *existing,
+                    duplicate: corner_id,
+                });
+            } else {
+                positions.insert(key, corner_id);
+            }
+        }
+        return output;
+    }
+
+    fn check_bounds(graph: &Graph) -> Vec<GraphViolation> {
+        let x_scale = graph.x_scale as f32;
+        let y_scale = graph.y_scale as f32;
+        return graph
+            .corner_ids()
+            .filter(|corner_id| {
+                let (x, y) = graph[*corner_id].pos;
+                !(0.0..=x_scale).contains(&x) || !(0.0..=y_scale).contains(&y)
+            })
+            .map(|corner| GraphViolation::OutOfBounds {
+                corner,
+                pos: graph[corner].pos,
+            })
+            .collect();
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        use crate::{config::config::GenerationConfig, graph2::graph2::generate_base_graph};
+
+        use super::*;
+
+        fn test_graph() -> Graph {
+            let config = GenerationConfig {
+                cell_count: 500,
+                ..Default::default()
+            };
+            return generate_base_graph(&config, &mut StdRng::seed_from_u64(8)).unwrap();
+        }
+
+        #[test]
+        fn generated_graph_is_valid_test() {
+            assert_eq!(test_graph().validate(), vec![]);
+        }
+
+        #[test]
+        fn broken_graph_violations_test() {
+            let mut graph = test_graph();
+            let edge_id = graph[CellId(0)].edges.remove(0);
+            graph[CornerId(1)].pos = (-1.0, 0.0);
+            let violations = graph.validate();
+            assert!(violations.contains(&GraphViolation::OpenCellRing { cell: CellId(0) }));
+            assert!(violations.contains(&GraphViolation::EdgeCellMismatch {
+                edge: edge_id,
+                cell: CellId(0)
+            }));
+            assert!(violations.contains(&GraphViolation::OutOfBounds {
+                corner: CornerId(1),
+                pos: (-1.0, 0.0)
+            }));
+        }
+    }
+}
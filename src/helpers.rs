@@ -22,11 +22,28 @@ pub mod helpers {
         return (x, y);
     }
 
+    /// Maps `f` over `0..len`, on rayon's thread pool with the `parallel`
+    /// feature. Results come back in index order either way, so callers get
+    /// the same output with or without the feature. A single threaded pool
+    /// takes the same sequential path as a build without the feature.
+    pub fn par_map<T, F>(len: usize, f: F) -> Vec<T>
+    where
+        T: Send,
+        F: Fn(usize) -> T + Send + Sync,
+    {
+        #[cfg(feature = "parallel")]
+        if rayon::current_num_threads() > 1 {
+            use rayon::prelude::*;
+            return (0..len).into_par_iter().map(f).collect();
+        }
+        return (0..len).map(f).collect();
+    }
+
     pub fn create_benchmarker(label: String) -> impl Fn() -> u128 {
         let label_padding: usize = 30;
         let epoch = Instant::now();
         return move || {
-            let duration = Instant::now().duration_since(epoch.clone()).as_millis();
+            let duration = Instant::now().duration_since(epoch).as_millis();
             println!(
                 "{}:{}{}ms",
                 label,
@@ -0,0 +1,14 @@
+#![allow(clippy::needless_return, clippy::module_inception)]
+pub mod config;
+pub mod error;
+pub mod export;
+pub mod graph;
+pub mod graph2;
+pub mod helpers;
+pub mod palette;
+#[cfg(feature = "viewer")]
+pub mod renderer;
+#[cfg(feature = "viewer")]
+pub mod renderer2;
+pub mod terrain2;
+pub mod voronoi;
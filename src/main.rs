@@ -1,49 +1,44 @@
-use graph2::graph2::Graph;
-pub mod graph;
-pub mod graph2;
-pub mod helpers;
-pub mod renderer;
-pub mod renderer2;
-pub mod terrain2;
-pub mod voronoi;
+#![allow(clippy::needless_return)]
 use nannou::prelude::*;
 use nannou_egui::{self, egui, Egui};
-use renderer2::renderer::render;
-use terrain2::terrain2::full_terrain_gen;
+use voronoi_map_gen::config::config::GenerationConfig;
+use voronoi_map_gen::graph2::graph2::Graph;
+use voronoi_map_gen::renderer2::renderer::render;
+use voronoi_map_gen::terrain2::terrain2::full_terrain_gen;
 
 // use crate::graph::graph::generate_base_diagram;
 
-pub const X_SCALE: f64 = 1600.0;
-pub const Y_SCALE: f64 = 800.0;
-
-// const I: usize = 2000;
-const I: usize = 2000;
-
 fn main() {
     nannou::app(model).update(update).run();
 }
 
 struct Model {
     graph: Graph,
+    config: GenerationConfig,
+    seed: u64,
     egui: Egui,
     has_logged_render: bool,
     log_render: bool,
 }
 
 fn model(app: &App) -> Model {
+    let config = GenerationConfig::default();
     let window = app
         .new_window()
-        .size_pixels((X_SCALE * 1.1) as u32, (Y_SCALE * 1.1) as u32)
+        .size_pixels((config.x_scale * 1.1) as u32, (config.y_scale * 1.1) as u32)
         .view(view)
         .raw_event(raw_window_event)
         .build()
         .unwrap();
     let window_a = app.window(window).unwrap();
-    let base_graph = full_terrain_gen(I, X_SCALE, Y_SCALE);
+    let seed = rand::random::<u64>();
+    let (base_graph, _report) = full_terrain_gen(&config, seed).expect("map generation failed");
     let egui = Egui::from_window(&window_a);
     // println!("Edge Cells: {}", base_graph.cells.values().filter(|cell| cell.data.ocean))
     Model {
         graph: base_graph,
+        config,
+        seed,
         egui,
         has_logged_render: false,
         log_render: true,
@@ -60,12 +55,18 @@ fn update(_app: &App, model: &mut Model, _update: Update) {
     }
 
     egui::Window::new("Settings").show(&ctx, |ui| {
+        ui.label(format!("Seed: {}", model.seed));
         let regenerate = ui.button("Regenerate").clicked();
         if regenerate {
-            let base_graph = full_terrain_gen(I, X_SCALE, Y_SCALE);
-            model.graph = base_graph;
-            model.log_render = true;
-            model.has_logged_render = false;
+            model.seed = rand::random::<u64>();
+            match full_terrain_gen(&model.config, model.seed) {
+                Ok((base_graph, _report)) => {
+                    model.graph = base_graph;
+                    model.log_render = true;
+                    model.has_logged_render = false;
+                }
+                Err(e) => eprintln!("Regeneration failed for seed {}: {}", model.seed, e),
+            }
         }
     });
 }
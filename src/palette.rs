@@ -0,0 +1,82 @@
+pub mod palette {
+    use crate::graph2::graph2::{Biome, Cell};
+
+    // colours are linear rgb, renderer2 hands them to nannou as `LinSrgb`
+    pub const FRESH_WATER: (f32, f32, f32) = (0.2, 0.33, 1.0);
+    pub const SALT_WATER: (f32, f32, f32) = (0.15, 0.25, 0.75);
+    // const EDGE: (f32, f32, f32) = (0.0, 0.0, 0.0);
+
+    const BEACH: (f32, f32, f32) = (0.62, 0.56, 0.46);
+    const ICE: (f32, f32, f32) = (0.6, 1.0, 1.0);
+    const MARSH: (f32, f32, f32) = (0.18, 0.4, 0.4);
+    const SNOW: (f32, f32, f32) = (1.0, 1.0, 1.0);
+    const TUNDRA: (f32, f32, f32) = (0.73, 0.73, 0.7);
+    const BARE: (f32, f32, f32) = (0.53, 0.53, 0.53);
+    const TAIGA: (f32, f32, f32) = (0.6, 0.66, 0.47);
+    const SHRUBLAND: (f32, f32, f32) = (0.53, 0.6, 0.47);
+    const TEMPERATEDESERT: (f32, f32, f32) = (0.79, 0.82, 0.61);
+    const TEMPERATERAINFOREST: (f32, f32, f32) = (0.26, 0.53, 0.33);
+    const TEMPERATEFOREST: (f32, f32, f32) = (0.4, 0.59, 0.34);
+    const GRASSLAND: (f32, f32, f32) = (0.53, 0.66, 0.34);
+    const SUBTROPICALDESERT: (f32, f32, f32) = (0.79, 0.73, 0.54);
+    const TROPICALRAINFOREST: (f32, f32, f32) = (0.2, 0.47, 0.33);
+    const TROPICALFOREST: (f32, f32, f32) = (0.33, 0.59, 0.26);
+
+    pub fn cell_colour(cell: &Cell) -> (f32, f32, f32) {
+        return match cell.biome {
+            Biome::Ocean => SALT_WATER,
+            Biome::Lake => FRESH_WATER,
+            Biome::Beach => BEACH,
+            Biome::Ice => ICE,
+            Biome::Marsh => MARSH,
+            Biome::Snow => SNOW,
+            Biome::Tundra => TUNDRA,
+            Biome::Bare => BARE,
+            Biome::Taiga => TAIGA,
+            Biome::Shrubland => SHRUBLAND,
+            Biome::TemperateDesert => TEMPERATEDESERT,
+            Biome::TemperateForest => TEMPERATEFOREST,
+            Biome::TemperateRainForest => TEMPERATERAINFOREST,
+            Biome::Grassland => GRASSLAND,
+            Biome::SubtropicalDesert => SUBTROPICALDESERT,
+            Biome::TropicalRainForest => TROPICALRAINFOREST,
+            Biome::TropicalForest => TROPICALFOREST,
+        };
+    }
+    pub fn cell_short(cell: &Cell) -> &str {
+        return match cell.biome {
+            Biome::Ocean => "",
+            Biome::Lake => "LKE",
+            Biome::Beach => "BCH",
+            Biome::Ice => "ICE",
+            Biome::Marsh => "MSH",
+            Biome::Snow => "SNW",
+            Biome::Tundra => "TUND",
+            Biome::Bare => "BARE",
+            Biome::Taiga => "TAIG",
+            Biome::Shrubland => "SHRUB",
+            Biome::TemperateDesert => "TDST",
+            Biome::TemperateForest => "TFST",
+            Biome::TemperateRainForest => "TRFST",
+            Biome::Grassland => "GSLD",
+            Biome::SubtropicalDesert => "STDST",
+            Biome::TropicalRainForest => "TRFST",
+            Biome::TropicalForest => "TRFST",
+        };
+    }
+
+    /// Encodes a linear palette colour as 8 bit sRGB, the way the viewer
+    /// shows it on screen
+    pub fn to_srgb8(colour: (f32, f32, f32)) -> [u8; 3] {
+        let encode = |c: f32| {
+            let c = c.clamp(0.0, 1.0);
+            let srgb = if c <= 0.0031308 {
+                c * 12.92
+            } else {
+                1.055 * c.powf(1.0 / 2.4) - 0.055
+            };
+            return (srgb * 255.0).round() as u8;
+        };
+        return [encode(colour.0), encode(colour.1), encode(colour.2)];
+    }
+}
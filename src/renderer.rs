@@ -6,8 +6,6 @@ pub mod renderer {
     use crate::graph::graph::Cell;
     use crate::graph::graph::Corner;
     use crate::graph::graph::Graph;
-    use crate::X_SCALE;
-    use crate::Y_SCALE;
 
     const FRESH_WATER: (f32, f32, f32) = (0.2, 0.33, 1.0);
     const SALT_WATER: (f32, f32, f32) = (0.2, 0.33, 1.0);
@@ -29,7 +27,14 @@ pub mod renderer {
     const TROPICALRAINFOREST: (f32, f32, f32) = (0.2, 0.47, 0.33);
     const TROPICALFOREST: (f32, f32, f32) = (0.33, 0.59, 0.26);
 
-    pub fn render(app: &App, frame: &Frame, graph: &Graph, biome_debug: bool) {
+    pub fn render(
+        app: &App,
+        frame: &Frame,
+        graph: &Graph,
+        biome_debug: bool,
+        x_scale: f32,
+        y_scale: f32,
+    ) {
         let draw = app.draw();
         draw.background().color(WHITE);
 
@@ -44,10 +49,7 @@ pub mod renderer {
                 let colour: LinSrgb<f32> = LinSrgb::from(cell_colour(cell));
 
                 return (
-                    (
-                        c.pos.0 - (X_SCALE as f32 / 2.0),
-                        c.pos.1 - (Y_SCALE as f32 / 2.0),
-                    ),
+                    (c.pos.0 - (x_scale / 2.0), c.pos.1 - (y_scale / 2.0)),
                     colour,
                 );
             });
@@ -77,19 +79,11 @@ pub mod renderer {
                 let mut p1 = graph.corners.get(&edge.corners.0).unwrap();
                 let mut p2 = graph.corners.get(&edge.corners.1).unwrap();
                 if !edge.corners.1.eq(&edge.down_corner) {
-                    let t = p1;
-                    p1 = p2;
-                    p2 = t;
+                    std::mem::swap(&mut p1, &mut p2);
                 }
 
-                let pt_1 = pt2(
-                    p1.pos.0 - (X_SCALE as f32 / 2.0),
-                    p1.pos.1 - (Y_SCALE as f32 / 2.0),
-                );
-                let pt_2 = pt2(
-                    p2.pos.0 - (X_SCALE as f32 / 2.0),
-                    p2.pos.1 - (Y_SCALE as f32 / 2.0),
-                );
+                let pt_1 = pt2(p1.pos.0 - (x_scale / 2.0), p1.pos.1 - (y_scale / 2.0));
+                let pt_2 = pt2(p2.pos.0 - (x_scale / 2.0), p2.pos.1 - (y_scale / 2.0));
 
                 if is_coast {
                     draw.line()
@@ -104,14 +98,14 @@ pub mod renderer {
                     draw.line()
                         .start(pt_1)
                         .end(pt_2)
-                        .weight((edge.data.river as f32).sqrt() * 0.3)
+                        .weight(edge.data.river.sqrt() * 0.3)
                         .color(LinSrgb::from(FRESH_WATER))
                         .caps_round()
                         .z(2.0);
                 }
             }
         }
-        draw.to_frame(app, &frame).unwrap();
+        draw.to_frame(app, frame).unwrap();
     }
 
     fn cell_colour(cell: &Cell) -> (f32, f32, f32) {
@@ -2,63 +2,187 @@ pub mod biome;
 pub mod edge_detail;
 pub mod elevation;
 pub mod island;
+pub mod pipeline;
+pub mod report;
 pub mod rivers;
+pub mod stage;
 pub mod terrain2 {
-    use std::time::Instant;
+    use rand::Rng;
 
     use crate::{
-        graph2::graph2::{generate_base_graph, Graph},
-        helpers::helpers::create_benchmarker,
+        config::config::GenerationConfig, error::error::MapGenError, graph2::graph2::Graph,
     };
 
     use super::{
-        biome::biome::assign_biomes,
-        edge_detail::edge_detail::add_edge_divisions,
-        elevation::elevation2::assign_land_elevation,
         island::island2::{assign_coastal_cells, assign_ocean_cells, run_island_gen},
-        rivers::rivers2::create_rivers,
+        pipeline::pipeline::run_all,
+        report::report::GenerationReport,
     };
 
-    pub fn run_terrain_gen(graph: &mut Graph) -> &mut Graph {
-        run_island_gen(graph);
-        assign_ocean_cells(graph);
-        assign_coastal_cells(graph);
-        return graph;
+    pub fn run_terrain_gen<'a>(
+        graph: &'a mut Graph,
+        config: &GenerationConfig,
+        rng: &mut impl Rng,
+    ) -> Result<&'a mut Graph, MapGenError> {
+        run_island_gen(graph, config, rng)?;
+        assign_ocean_cells(graph)?;
+        assign_coastal_cells(graph)?;
+        return Ok(graph);
     }
 
-    pub fn full_terrain_gen(i: usize, x_scale: f64, y_scale: f64) -> Graph {
-        let base_graph_gen = create_benchmarker(String::from("Base Graph Gen"));
-        let mut graph = generate_base_graph(i, x_scale, y_scale);
-        base_graph_gen();
+    /// Generates a full map, with a report of how long each stage took and
+    /// what the map holds. All randomness is drawn from a single rng seeded
+    /// with `seed`, so the same seed and parameters always produce the same graph.
+    /// Fails early if `config` is out of range, or if any stage breaks the graph.
+    /// See `Pipeline` to keep each stage's output and re-run part of it.
+    pub fn full_terrain_gen(
+        config: &GenerationConfig,
+        seed: u64,
+    ) -> Result<(Graph, GenerationReport), MapGenError> {
+        return run_all(config, seed);
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::{
+            graph2::mask::mask::BoundaryMask, terrain2::island::island2::find_border_cell_ids,
+        };
 
-        let island_gen = create_benchmarker(String::from("Island Gen"));
-        run_island_gen(&mut graph);
-        island_gen();
+        fn test_config() -> GenerationConfig {
+            return GenerationConfig {
+                cell_count: 500,
+                ..Default::default()
+            };
+        }
 
-        let assign_ocean = create_benchmarker(String::from("Ocean Assign"));
-        assign_ocean_cells(&mut graph);
-        assign_ocean();
+        #[test]
+        fn full_terrain_gen_is_reproducible_test() {
+            let a = full_terrain_gen(&test_config(), 42).unwrap().0;
+            let b = full_terrain_gen(&test_config(), 42).unwrap().0;
+            assert_eq!(a, b);
+        }
 
-        let assign_coastal = create_benchmarker(String::from("Coastal Assign"));
-        assign_coastal_cells(&mut graph);
-        assign_coastal();
+        // par_map runs sequentially on a one thread pool, as it does without
+        // the feature
+        #[cfg(feature = "parallel")]
+        #[test]
+        fn parallel_matches_sequential_test() {
+            let run = |threads: usize| {
+                let pool = rayon::ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .build()
+                    .unwrap();
+                return pool.install(|| full_terrain_gen(&test_config(), 42).unwrap().0);
+            };
+            let sequential = run(1).to_bytes();
+            assert_eq!(run(4).to_bytes(), sequential);
+            assert_eq!(run(8).to_bytes(), sequential);
+        }
 
-        let assign_elevation = create_benchmarker(String::from("Elevation Assign"));
-        assign_land_elevation(&mut graph);
-        assign_elevation();
+        #[test]
+        fn full_terrain_gen_seeds_differ_test() {
+            let a = full_terrain_gen(&test_config(), 1).unwrap().0;
+            let b = full_terrain_gen(&test_config(), 2).unwrap().0;
+            assert_ne!(a, b);
+        }
 
-        let assign_rivers = create_benchmarker(String::from("Create Rivers"));
-        create_rivers(&mut graph);
-        assign_rivers();
+        #[test]
+        fn full_terrain_gen_respects_config_test() {
+            let config = GenerationConfig {
+                cell_count: 300,
+                x_scale: 400.0,
+                y_scale: 300.0,
+                edge_subdivisions: 1,
+                ..Default::default()
+            };
+            let graph = full_terrain_gen(&config, 7).unwrap().0;
+            assert_eq!(graph.cells.len(), 300);
+            assert!(graph
+                .corners
+                .iter()
+                .all(|c| c.pos.0 <= 400.0 && c.pos.1 <= 300.0));
+            assert!(graph.edges.iter().all(|e| e.corner_midpoints.len() == 3));
+        }
 
-        let biome_assign = create_benchmarker(String::from("Assign Biomes"));
-        assign_biomes(&mut graph);
-        biome_assign();
+        #[test]
+        fn wrap_x_joins_the_seam_test() {
+            let config = GenerationConfig {
+                wrap_x: true,
+                ..test_config()
+            };
+            let graph = full_terrain_gen(&config, 3).unwrap().0;
+            let half_width = graph.x_scale as f32 / 2.0;
+            let on_y_border = |pos: (f32, f32)| pos.1 == 0.0 || pos.1 == graph.y_scale as f32;
+            assert!(graph
+                .edges
+                .iter()
+                .any(|e| (graph[e.corners.0].pos.0 - graph[e.corners.1].pos.0).abs() > half_width));
+            // only the north and south edges of the map are left open
+            for edge in &graph.edges {
+                if edge.cells.len() < 2 {
+                    assert!(on_y_border(graph[edge.corners.0].pos));
+                    assert!(on_y_border(graph[edge.corners.1].pos));
+                }
+            }
+            for cell_id in find_border_cell_ids(&graph) {
+                assert!(graph
+                    .get_cell_corners(cell_id)
+                    .iter()
+                    .any(|c| on_y_border(c.pos)));
+            }
+        }
 
-        let edge_divisions = create_benchmarker(String::from("Edge Divisions"));
-        add_edge_divisions(&mut graph);
-        edge_divisions();
+        #[test]
+        fn boundary_mask_makes_outside_cells_ocean_test() {
+            let diamond = vec![(800.0, 0.0), (1600.0, 400.0), (800.0, 800.0), (0.0, 400.0)];
+            let config = GenerationConfig {
+                boundary: BoundaryMask::Polygon(diamond),
+                ..test_config()
+            };
+            let graph = full_terrain_gen(&config, 5).unwrap().0;
+            let inside = |pos: (f32, f32)| {
+                return (pos.0 - 800.0).abs() / 800.0 + (pos.1 - 400.0).abs() / 400.0 <= 1.0;
+            };
+            assert!(graph.cells.iter().any(|cell| !cell.water));
+            for cell_id in graph.cell_ids() {
+                if !inside(graph.get_cell_center(cell_id)) {
+                    assert!(graph[cell_id].ocean);
+                }
+            }
+        }
 
-        return graph;
+        #[test]
+        fn full_terrain_gen_rejects_invalid_config_test() {
+            let too_few_cells = GenerationConfig {
+                cell_count: 0,
+                ..Default::default()
+            };
+            let negative_scale = GenerationConfig {
+                x_scale: -10.0,
+                ..Default::default()
+            };
+            let short_image = GenerationConfig {
+                boundary: BoundaryMask::Image {
+                    width: 4,
+                    height: 4,
+                    pixels: vec![255; 8],
+                    threshold: 128,
+                },
+                ..Default::default()
+            };
+            assert!(matches!(
+                full_terrain_gen(&too_few_cells, 1),
+                Err(MapGenError::InvalidConfig(_))
+            ));
+            assert!(matches!(
+                full_terrain_gen(&negative_scale, 1),
+                Err(MapGenError::InvalidConfig(_))
+            ));
+            assert!(matches!(
+                full_terrain_gen(&short_image, 1),
+                Err(MapGenError::InvalidConfig(_))
+            ));
+        }
     }
 }
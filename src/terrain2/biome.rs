@@ -1,136 +1,235 @@
 pub mod biome {
-    use std::collections::{HashMap, HashSet};
-
-    use uuid::Uuid;
+    use std::{
+        cmp::Ordering,
+        collections::{BTreeSet, BinaryHeap},
+    };
 
     use crate::{
-        graph2::graph2::{Biome, Cell, Corner, Graph},
-        helpers::helpers::corner_distance2,
-        X_SCALE,
+        config::config::GenerationConfig,
+        error::error::MapGenError,
+        graph2::graph2::{Biome, Cell, CellId, CornerId, Graph},
+        helpers::helpers::par_map,
     };
 
-    const MOISTURE_FALLOFF: f32 = 0.05;
-
-    fn get_fresh_water_corners(graph: &Graph) -> Vec<&Corner> {
-        let mut output: HashSet<Uuid> = HashSet::new();
-        for (edge_id, edge) in &graph.edges {
+    // corners on a river, or on the shore of a lake
+    fn get_fresh_water_corners(graph: &Graph) -> BTreeSet<CornerId> {
+        let mut output: BTreeSet<CornerId> = BTreeSet::new();
+        for edge in &graph.edges {
             // river case
             if edge.river > 0.0 {
-                output.insert(edge.corners.0.clone());
-                output.insert(edge.corners.1.clone());
+                output.insert(edge.corners.0);
+                output.insert(edge.corners.1);
             } else {
                 // lake border case
-                let cells: Vec<&Cell> = edge
-                    .cells
-                    .iter()
-                    .map(|c_id| graph.cells.get(c_id).unwrap())
-                    .collect();
+                let cells: Vec<&Cell> = edge.cells.iter().map(|c_id| &graph[*c_id]).collect();
                 if cells.iter().any(|cell| cell.water && !cell.ocean)
                     && cells.iter().any(|cell| !cell.water)
                 {
-                    output.insert(edge.corners.0.clone());
-                    output.insert(edge.corners.1.clone());
+                    output.insert(edge.corners.0);
+                    output.insert(edge.corners.1);
                 }
             }
         }
+        return output;
+    }
 
-        return output
-            .iter()
-            .map(|id| graph.corners.get(id).unwrap())
-            .collect();
+    // a corner waiting in the flood. The heap pops the nearest first, and the
+    // lowest id on a tie so the order never depends on insertion
+    #[derive(Debug, PartialEq)]
+    struct Visit {
+        distance: f32,
+        corner_id: CornerId,
     }
 
-    fn assign_moisture(graph: &mut Graph) -> &mut Graph {
-        let graph_clone = graph.clone();
-        let fresh_water_corners = get_fresh_water_corners(&graph_clone);
-        // pre calculate all corner moisture levels
-        let mut corner_moisture_cache: HashMap<Uuid, f32> = HashMap::new();
-        for (corner_id, corner) in &graph_clone.corners {
-            let shortest_distance = fresh_water_corners.iter().fold(X_SCALE as f32, |acc, c| {
-                let d = corner_distance2(corner, c);
-                if d < acc {
-                    return d;
-                } else {
-                    return acc;
-                }
+    impl Eq for Visit {}
+
+    impl Ord for Visit {
+        fn cmp(&self, other: &Self) -> Ordering {
+            return other
+                .distance
+                .total_cmp(&self.distance)
+                .then_with(|| other.corner_id.cmp(&self.corner_id));
+        }
+    }
+
+    impl PartialOrd for Visit {
+        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+            return Some(self.cmp(other));
+        }
+    }
+
+    /// Distance from every corner to the nearest fresh water, walking along
+    /// edges rather than straight across the map. Each edge costs its length
+    /// plus `elevation_weight` times the change in elevation along it.
+    /// Corners with no way to fresh water are left at infinity.
+    fn fresh_water_distances(graph: &Graph, elevation_weight: f32) -> Vec<f32> {
+        let mut distances = vec![f32::INFINITY; graph.corners.len()];
+        let mut queue: BinaryHeap<Visit> = BinaryHeap::new();
+        for corner_id in get_fresh_water_corners(graph) {
+            distances[corner_id.index()] = 0.0;
+            queue.push(Visit {
+                distance: 0.0,
+                corner_id,
             });
-            corner_moisture_cache
-                .insert(corner_id.clone(), MOISTURE_FALLOFF.powf(shortest_distance));
         }
-        for (c_id, c) in &graph_clone.cells {
-            if !c.water {
-                let cell = graph.cells.get_mut(c_id).unwrap();
-                let corners = graph_clone.get_cell_corners_ids(c_id);
-                let cell_moisture_total = corners.iter().fold(0.0, |acc, corner| {
-                    acc + corner_moisture_cache.get(corner).unwrap()
-                });
-                cell.moisture = cell_moisture_total / corners.len() as f32;
-                drop(cell);
+        while let Some(Visit {
+            distance,
+            corner_id,
+        }) = queue.pop()
+        {
+            // already reached by a shorter path
+            if distance > distances[corner_id.index()] {
+                continue;
+            }
+            let corner = &graph[corner_id];
+            for edge_id in &corner.edges {
+                let next_id = graph.get_edge_other_corner(*edge_id, corner_id);
+                let next = &graph[next_id];
+                let cost = graph.pos_distance(corner.pos, next.pos)
+                    + elevation_weight * (next.elevation - corner.elevation).abs();
+                if distance + cost < distances[next_id.index()] {
+                    distances[next_id.index()] = distance + cost;
+                    queue.push(Visit {
+                        distance: distance + cost,
+                        corner_id: next_id,
+                    });
+                }
+            }
+        }
+        return distances;
+    }
+
+    fn assign_moisture<'a>(graph: &'a mut Graph, config: &GenerationConfig) -> &'a mut Graph {
+        let distances = fresh_water_distances(graph, config.moisture_elevation_weight);
+        // moisture never falls below what it would be a whole map width away
+        let corner_moisture_cache: Vec<f32> = distances
+            .iter()
+            .map(|d| config.moisture_falloff.powf(d.min(graph.x_scale as f32)))
+            .collect();
+        let cell_moisture: Vec<Option<f32>> = par_map(graph.cells.len(), |i| {
+            let c_id = CellId(i as u32);
+            if graph[c_id].water {
+                return None;
+            }
+            let corners = graph.get_cell_corners_ids(c_id);
+            let cell_moisture_total = corners.iter().fold(0.0, |acc, corner| {
+                acc + corner_moisture_cache[corner.index()]
+            });
+            return Some(cell_moisture_total / corners.len() as f32);
+        });
+        for (cell, moisture) in graph.cells.iter_mut().zip(cell_moisture) {
+            if let Some(moisture) = moisture {
+                cell.moisture = moisture;
             }
         }
         return graph;
     }
 
-    pub fn assign_biomes(graph: &mut Graph) -> &mut Graph {
-        assign_moisture(graph);
-        let graph_clone = graph.clone();
-        let cell_clone = graph.cells.clone();
-        for c_id in cell_clone.keys() {
-            let cell = graph.cells.get_mut(c_id).unwrap();
-            if cell.ocean {
-                cell.biome = Biome::Ocean;
-            } else if cell.water {
-                // Water
-                if graph_clone.get_cell_elevation(&c_id) < 0.1 {
-                    cell.biome = Biome::Marsh;
-                } else if graph_clone.get_cell_elevation(&c_id) > 0.8 {
-                    cell.biome = Biome::Ice;
-                } else {
-                    cell.biome = Biome::Lake;
-                }
-            } else if cell.coast {
-                cell.biome = Biome::Beach;
-            } else if graph_clone.get_cell_elevation(&c_id) > 0.8 {
-                // High Altitude
-                if cell.moisture > 0.66 {
-                    cell.biome = Biome::Snow;
-                } else if cell.moisture > 0.33 {
-                    cell.biome = Biome::Tundra;
-                } else {
-                    cell.biome = Biome::Bare;
-                }
-            } else if graph_clone.get_cell_elevation(&c_id) > 0.6 {
-                // Middle-High Altitude
-                if cell.moisture > 0.66 {
-                    cell.biome = Biome::Taiga;
-                } else if cell.moisture > 0.33 {
-                    cell.biome = Biome::Shrubland;
-                } else {
-                    cell.biome = Biome::TemperateDesert;
-                }
-            } else if graph_clone.get_cell_elevation(&c_id) > 0.3 {
-                // Middle-Low Altitude
-                if cell.moisture > 0.66 {
-                    cell.biome = Biome::TemperateRainForest;
-                } else if cell.moisture > 0.33 {
-                    cell.biome = Biome::TemperateForest;
-                } else {
-                    cell.biome = Biome::Grassland;
-                }
+    fn cell_biome(cell: &Cell, elevation: f32) -> Biome {
+        if cell.ocean {
+            return Biome::Ocean;
+        } else if cell.water {
+            // Water
+            if elevation < 0.1 {
+                return Biome::Marsh;
+            } else if elevation > 0.8 {
+                return Biome::Ice;
+            } else {
+                return Biome::Lake;
+            }
+        } else if cell.coast {
+            return Biome::Beach;
+        } else if elevation > 0.8 {
+            // High Altitude
+            if cell.moisture > 0.66 {
+                return Biome::Snow;
+            } else if cell.moisture > 0.33 {
+                return Biome::Tundra;
+            } else {
+                return Biome::Bare;
+            }
+        } else if elevation > 0.6 {
+            // Middle-High Altitude
+            if cell.moisture > 0.66 {
+                return Biome::Taiga;
+            } else if cell.moisture > 0.33 {
+                return Biome::Shrubland;
+            } else {
+                return Biome::TemperateDesert;
+            }
+        } else if elevation > 0.3 {
+            // Middle-Low Altitude
+            if cell.moisture > 0.66 {
+                return Biome::TemperateRainForest;
+            } else if cell.moisture > 0.33 {
+                return Biome::TemperateForest;
+            } else {
+                return Biome::Grassland;
+            }
+        } else {
+            // Low Altitude
+            if cell.moisture > 0.66 {
+                return Biome::TropicalRainForest;
+            } else if cell.moisture > 0.33 {
+                return Biome::TemperateForest;
+            } else if cell.moisture > 0.15 {
+                return Biome::Grassland;
             } else {
-                // Low Altitude
-                if cell.moisture > 0.66 {
-                    cell.biome = Biome::TropicalRainForest;
-                } else if cell.moisture > 0.33 {
-                    cell.biome = Biome::TemperateForest;
-                } else if cell.moisture > 0.15 {
-                    cell.biome = Biome::Grassland;
-                } else {
-                    cell.biome = Biome::SubtropicalDesert;
+                return Biome::SubtropicalDesert;
+            }
+        }
+    }
+
+    pub fn assign_biomes<'a>(
+        graph: &'a mut Graph,
+        config: &GenerationConfig,
+    ) -> Result<&'a mut Graph, MapGenError> {
+        assign_moisture(graph, config);
+        let biomes = par_map(graph.cells.len(), |i| {
+            let c_id = CellId(i as u32);
+            return cell_biome(&graph[c_id], graph.get_cell_elevation(c_id));
+        });
+        for (cell, biome) in graph.cells.iter_mut().zip(biomes) {
+            cell.biome = biome;
+        }
+        return Ok(graph);
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::terrain2::{pipeline::pipeline::Pipeline, stage::stage::default_stages};
+
+        #[test]
+        fn fresh_water_distances_test() {
+            let config = GenerationConfig {
+                cell_count: 500,
+                ..Default::default()
+            };
+            let stages = default_stages(&config);
+            let pipeline = Pipeline::with_snapshots(config, 8, stages, "biomes").unwrap();
+            let graph = pipeline.stage_graph("rivers").unwrap();
+            let sources = get_fresh_water_corners(graph);
+            assert!(!sources.is_empty());
+
+            let flat = fresh_water_distances(graph, 0.0);
+            let weighted = fresh_water_distances(graph, 100.0);
+            for corner_id in graph.corner_ids() {
+                let i = corner_id.index();
+                // a path along edges is never shorter than a straight line
+                let straight = sources
+                    .iter()
+                    .map(|id| graph.pos_distance(graph[corner_id].pos, graph[*id].pos))
+                    .fold(f32::INFINITY, f32::min);
+                assert!(flat[i] >= straight - 1e-3);
+                assert!(weighted[i] >= flat[i]);
+                if sources.contains(&corner_id) {
+                    assert_eq!(flat[i], 0.0);
                 }
             }
-            drop(cell);
+            assert!(graph
+                .corner_ids()
+                .any(|id| weighted[id.index()] > flat[id.index()]));
         }
-        return graph;
     }
 }
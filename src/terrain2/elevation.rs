@@ -1,60 +1,55 @@
 pub mod elevation2 {
-    use std::collections::{HashSet, VecDeque};
+    use std::collections::{BTreeSet, HashSet, VecDeque};
 
-    use uuid::Uuid;
+    use crate::{
+        error::error::MapGenError,
+        graph2::graph2::{Cell, CornerId, EdgeId, Graph},
+    };
 
-    use crate::graph2::graph2::{Cell, Corner, Edge, Graph};
-
-    fn get_coastal_corners(graph: &Graph) -> Vec<Uuid> {
-        let coastal_cells: Vec<(&uuid::Uuid, &Cell)> = graph
-            .cells
-            .iter()
-            .filter(|(_id, cell)| cell.coast)
-            .collect();
-        let coastal_cell_edges_nested: Vec<Vec<Uuid>> = coastal_cells
+    fn get_coastal_corners(graph: &Graph) -> Vec<CornerId> {
+        let coastal_cells: Vec<&Cell> = graph.cells.iter().filter(|cell| cell.coast).collect();
+        let coastal_cell_edges_nested: Vec<Vec<EdgeId>> = coastal_cells
             .iter()
-            .map(|(_id, cell)| cell.edges.clone())
+            .map(|cell| cell.edges.clone())
             .collect();
 
-        let mut coastal_cell_edge_ids: HashSet<Uuid> = HashSet::new();
+        let mut coastal_cell_edge_ids: BTreeSet<EdgeId> = BTreeSet::new();
         for id in coastal_cell_edges_nested.concat() {
             coastal_cell_edge_ids.insert(id);
         }
         let mut coastal_edges = coastal_cell_edge_ids.clone();
         coastal_edges.retain(|id| {
-            let edge = graph.edges.get(id).unwrap();
-            let cells: Vec<&Cell> = edge
+            let cells: Vec<&Cell> = graph[*id]
                 .cells
                 .iter()
-                .map(|cell_id| graph.cells.get(cell_id).unwrap())
+                .map(|cell_id| &graph[*cell_id])
                 .collect();
             return cells.iter().any(|cell| cell.ocean) && cells.iter().any(|cell| cell.coast);
         });
-        let nested_corners: Vec<Vec<Uuid>> = coastal_edges
+        let nested_corners: Vec<Vec<CornerId>> = coastal_edges
             .iter()
             .map(|edge_id| {
-                let edge = graph.edges.get(edge_id).unwrap();
-                return vec![edge.corners.0.clone(), edge.corners.1.clone()];
+                let edge = &graph[*edge_id];
+                return vec![edge.corners.0, edge.corners.1];
             })
             .collect();
 
         return nested_corners.concat();
     }
 
-    pub fn assign_land_elevation(graph: &mut Graph) -> &mut Graph {
-        let graph_clone = graph.clone();
-        let coastal_corner_ids = get_coastal_corners(&graph_clone);
-        let coastal_corners: Vec<(Uuid, f32)> =
+    pub fn assign_land_elevation(graph: &mut Graph) -> Result<&mut Graph, MapGenError> {
+        let coastal_corner_ids = get_coastal_corners(graph);
+        let coastal_corners: Vec<(CornerId, f32)> =
             coastal_corner_ids.iter().map(|id| (*id, 0.0)).collect();
 
-        let mut queue: VecDeque<(Uuid, f32)> = VecDeque::from(coastal_corners);
-        let mut processed: HashSet<Uuid> = HashSet::new();
+        let mut queue: VecDeque<(CornerId, f32)> = VecDeque::from(coastal_corners);
+        let mut processed: HashSet<CornerId> = HashSet::new();
         for (id, _e) in &queue {
-            processed.insert(id.clone());
+            processed.insert(*id);
         }
         while let Some((id, base_elevation)) = queue.pop_front() {
-            processed.insert(id.clone());
-            let corner_cells = graph_clone.get_corner_cells(&id);
+            processed.insert(id);
+            let corner_cells = graph.get_corner_cells(id);
 
             let is_open_water_corner = corner_cells.iter().all(|(_id, cell)| cell.water);
 
@@ -71,32 +66,32 @@ pub mod elevation2 {
             };
             let new_elev: f32 = base_elevation + elev_change;
 
-            let adjacent_corners = graph.get_corner_adjacent_corners(&id);
+            let adjacent_corners = graph.get_corner_adjacent_corners(id);
             for (c_id, _c) in adjacent_corners {
                 if !c_id.eq(&id) && !processed.contains(&c_id) {
-                    queue.push_back((c_id.clone(), new_elev.clone()));
-                    processed.insert(c_id.clone());
+                    queue.push_back((c_id, new_elev));
+                    processed.insert(c_id);
                 }
             }
-            let corner_mut = graph.corners.get_mut(&id).unwrap();
-            corner_mut.elevation = new_elev;
-            drop(corner_mut);
+            graph[id].elevation = new_elev;
         }
         normalise_elevation(graph);
-        return graph;
+        return Ok(graph);
     }
 
     fn normalise_elevation(graph: &mut Graph) -> &mut Graph {
-        let graph_clone = graph.clone();
         let max_elev = graph
             .corners
-            .values()
+            .iter()
             .map(|c| c.elevation)
             .fold(0.0, |acc, elev| if elev > acc { elev } else { acc });
-        for c_id in graph_clone.corners.keys() {
-            let corner_mut = graph.corners.get_mut(c_id).unwrap();
-            corner_mut.elevation /= max_elev;
-            drop(corner_mut);
+        // an all-ocean map has no land to scale, and dividing by zero would
+        // fill every corner with NaN
+        if max_elev <= 0.0 {
+            return graph;
+        }
+        for c_id in graph.corner_ids() {
+            graph[c_id].elevation /= max_elev;
         }
         return graph;
     }
@@ -1,132 +1,206 @@
 pub mod island2 {
+    use std::collections::BTreeSet;
     use std::collections::HashSet;
     use std::collections::VecDeque;
+    use std::f64::consts::TAU;
 
+    use crate::config::config::GenerationConfig;
+    use crate::error::error::MapGenError;
     use crate::graph2::graph2::Biome;
-    use crate::{graph2::graph2::Graph, X_SCALE, Y_SCALE};
-    use nannou::noise::NoiseFn;
-    use nannou::noise::Perlin;
-    use nannou::noise::Seedable;
-    use rand::RngCore;
-    use uuid::Uuid;
+    use crate::graph2::graph2::{CellId, CornerId, Graph};
+    use crate::helpers::helpers::par_map;
+    use noise::{NoiseFn, Perlin};
+    use rand::Rng;
 
-    const NOISE_SCALE: f32 = 4.0;
-
-    const WATER_COVERAGE_MODIFIER: f64 = 1.0;
-
-    fn find_border_corner_ids(graph: &Graph) -> HashSet<&Uuid> {
-        let mut output: HashSet<&Uuid> = HashSet::new();
-        for (corner_id, corner) in &graph.corners {
-            if corner.pos.0.eq(&(X_SCALE as f32))
-                || corner.pos.0.eq(&0.0)
-                || corner.pos.1.eq(&(Y_SCALE as f32))
-                || corner.pos.1.eq(&0.0)
-            {
-                output.insert(&corner_id);
+    fn find_border_corner_ids(graph: &Graph) -> HashSet<CornerId> {
+        let mut output: HashSet<CornerId> = HashSet::new();
+        // a planet has no border at all
+        if graph.sphere {
+            return output;
+        }
+        for corner_id in graph.corner_ids() {
+            let corner = &graph[corner_id];
+            // a wrapping map only has a north and south border
+            let on_x_border = !graph.wrap_x
+                && (corner.pos.0.eq(&(graph.x_scale as f32)) || corner.pos.0.eq(&0.0));
+            if on_x_border || corner.pos.1.eq(&(graph.y_scale as f32)) || corner.pos.1.eq(&0.0) {
+                output.insert(corner_id);
             }
         }
         return output;
     }
 
-    pub fn find_border_cell_ids(graph: &Graph) -> HashSet<&Uuid> {
-        let mut output: HashSet<&Uuid> = HashSet::new();
+    /// Cells touching the edge of the map, or lying outside its boundary mask
+    pub fn find_border_cell_ids(graph: &Graph) -> BTreeSet<CellId> {
+        let mut output: BTreeSet<CellId> = BTreeSet::new();
         let border_corners = find_border_corner_ids(graph);
-        for (cell_id, _cell) in &graph.cells {
+        for cell_id in graph.cell_ids() {
             let cell_corners = graph.get_cell_corners_ids(cell_id);
-            let is_border = cell_corners.iter().any(|id| border_corners.contains(id));
+            let center = graph.wrap_pos(graph.get_cell_center(cell_id));
+            let is_border = cell_corners.iter().any(|id| border_corners.contains(id))
+                || !graph
+                    .boundary
+                    .contains(center, graph.x_scale, graph.y_scale);
             if is_border {
-                output.insert(&cell_id);
+                output.insert(cell_id);
             }
         }
         return output;
     }
 
-    pub fn run_island_gen(graph: &mut Graph) -> &mut Graph {
-        let graph_clone = graph.clone();
+    // whether the noise, pulled down towards the map edges, leaves the cell
+    // under water
+    fn cell_is_water(
+        graph: &Graph,
+        config: &GenerationConfig,
+        perlin: &Perlin,
+        id: CellId,
+    ) -> bool {
+        let (x_b, y_b) = graph.get_cell_center(id);
+        let x = (x_b + 1.0) / graph.x_scale as f32;
+        let y = (y_b + 1.0) / graph.y_scale as f32;
 
-        let mut rng = rand::thread_rng();
-        let seed = rng.next_u32();
-        let perlin = Perlin::new().set_seed(seed);
+        let perlin_pos_value = if graph.sphere {
+            let (px, py, pz) = graph.get_cell_center_3d(id);
+            let scale = config.noise_scale as f64;
+            ((perlin.get([px as f64 * scale, py as f64 * scale, pz as f64 * scale])) + 1.0) / 2.0
+        } else if graph.wrap_x {
+            // sample around a cylinder with the same circumference as
+            // the flat sample width, so the noise joins up at the seam
+            let angle = x as f64 * TAU;
+            let radius = config.noise_scale as f64 / TAU;
+            ((perlin.get([
+                radius * angle.cos(),
+                radius * angle.sin(),
+                (y * config.noise_scale) as f64,
+            ])) + 1.0)
+                / 2.0
+        } else {
+            ((perlin.get([
+                (x * config.noise_scale) as f64,
+                (y * config.noise_scale) as f64,
+            ])) + 1.0)
+                / 2.0
+        };
 
-        let cell_ids = graph_clone.cells.keys();
-        let edge_cell_ids = find_border_cell_ids(&graph_clone);
-        for id in cell_ids {
-            let cell = graph.cells.get_mut(id).unwrap();
-            if edge_cell_ids.contains(&id) {
-                cell.water = true;
-                cell.ocean = false;
-                cell.biome = Biome::Ocean;
-                drop(cell);
-            } else {
-                let (x_b, y_b) = graph_clone.get_cell_center(id);
-                let x = (x_b + 1.0) / X_SCALE as f32;
-                let y = (y_b + 1.0) / Y_SCALE as f32;
+        let edge_distances: &[f32] = if graph.sphere {
+            &[]
+        } else if graph.wrap_x {
+            &[y - 1.0, y]
+        } else {
+            &[x - 1.0, x, y - 1.0, y]
+        };
+        let min_edge_distance = edge_distances
+            .iter()
+            .fold(1.0, |acc, d| if d.abs() < acc { d.abs() } else { acc })
+            .abs();
 
-                let perlin_pos_value =
-                    ((perlin.get([(x * NOISE_SCALE) as f64, (y * NOISE_SCALE) as f64])) + 1.0)
-                        / 2.0;
+        let edge_penalty = -((min_edge_distance - 0.1).min(0.0) * 5.0);
+        return (perlin_pos_value * config.water_coverage_modifier) < (0.5 + edge_penalty as f64);
+    }
 
-                let edge_distances = vec![x - 1.0, x, y - 1.0, y];
-                let min_edge_distance = edge_distances
-                    .iter()
-                    .fold(1.0, |acc, d| if d.abs() < acc { d.abs() } else { acc })
-                    .abs();
+    pub fn run_island_gen<'a>(
+        graph: &'a mut Graph,
+        config: &GenerationConfig,
+        rng: &mut impl Rng,
+    ) -> Result<&'a mut Graph, MapGenError> {
+        let seed = rng.next_u32();
+        let perlin = Perlin::new(seed);
 
-                let edge_penalty = (min_edge_distance - 0.1).min(0.0) * 5.0 * -1.0;
-                if (perlin_pos_value * WATER_COVERAGE_MODIFIER) < (0.5 + edge_penalty as f64) {
+        let edge_cell_ids = find_border_cell_ids(graph);
+        // border cells are left as None, they are always ocean
+        let sampled: Vec<Option<bool>> = par_map(graph.cells.len(), |i| {
+            let id = CellId(i as u32);
+            if edge_cell_ids.contains(&id) {
+                return None;
+            }
+            return Some(cell_is_water(graph, config, &perlin, id));
+        });
+        for (cell, water) in graph.cells.iter_mut().zip(sampled) {
+            match water {
+                None => {
+                    cell.water = true;
+                    cell.ocean = false;
+                    cell.biome = Biome::Ocean;
+                }
+                Some(true) => {
                     cell.water = true;
                     cell.biome = Biome::Lake;
-                    drop(cell);
-                } else {
-                    cell.water = false;
-                    drop(cell);
+                }
+                Some(false) => cell.water = false,
+            }
+        }
+        return Ok(graph);
+    }
+
+    // the cells of the largest connected body of water, lowest id first on a tie
+    fn find_largest_water_body(graph: &Graph) -> BTreeSet<CellId> {
+        let mut largest: BTreeSet<CellId> = BTreeSet::new();
+        let mut seen: HashSet<CellId> = HashSet::new();
+        for start in graph.cell_ids() {
+            if !graph[start].water || seen.contains(&start) {
+                continue;
+            }
+            let mut body: BTreeSet<CellId> = BTreeSet::from([start]);
+            let mut queue: VecDeque<CellId> = VecDeque::from([start]);
+            seen.insert(start);
+            while let Some(id) = queue.pop_front() {
+                for n_cell_id in graph.get_cell_adjacent_cells(id) {
+                    if graph[n_cell_id].water && seen.insert(n_cell_id) {
+                        body.insert(n_cell_id);
+                        queue.push_back(n_cell_id);
+                    }
                 }
             }
+            if body.len() > largest.len() {
+                largest = body;
+            }
         }
-        return graph;
+        return largest;
     }
 
-    pub fn assign_ocean_cells(graph: &mut Graph) -> &mut Graph {
-        let mut graph_clone = graph.clone();
-        let edge_cell_ids = find_border_cell_ids(&graph_clone);
-        let mut queue: VecDeque<&Uuid> = VecDeque::from_iter(edge_cell_ids.iter().map(|i| *i));
-        let mut processed: HashSet<&Uuid> = HashSet::new();
+    pub fn assign_ocean_cells(graph: &mut Graph) -> Result<&mut Graph, MapGenError> {
+        let mut edge_cell_ids = find_border_cell_ids(graph);
+        // with no map border to flood in from, the biggest sea is the ocean
+        if edge_cell_ids.is_empty() {
+            edge_cell_ids = find_largest_water_body(graph);
+        }
+        let mut queue: VecDeque<CellId> = VecDeque::from_iter(edge_cell_ids.iter().copied());
+        let mut processed: HashSet<CellId> = HashSet::new();
         for id in &queue {
-            processed.insert(id);
+            processed.insert(*id);
         }
         while let Some(id) = queue.pop_front() {
-            for n_cell_id in graph_clone.get_cell_adjacent_cells(&id) {
-                let n_cell = graph_clone.cells.get(n_cell_id).unwrap();
+            for n_cell_id in graph.get_cell_adjacent_cells(id) {
+                let n_cell = &graph[n_cell_id];
                 if n_cell.water && !processed.contains(&n_cell_id) {
                     processed.insert(n_cell_id);
-                    queue.push_back(&n_cell_id);
+                    queue.push_back(n_cell_id);
                 }
             }
 
-            let cell_mut = graph.cells.get_mut(id).unwrap();
+            let cell_mut = &mut graph[id];
             cell_mut.ocean = true;
             cell_mut.biome = Biome::Ocean;
-            drop(cell_mut);
         }
-        return graph;
+        return Ok(graph);
     }
 
-    pub fn assign_coastal_cells(graph: &mut Graph) -> &mut Graph {
-        let graph_clone = graph.clone();
-        for cell_id in graph_clone.cells.keys() {
-            let mut cell = graph.cells.get_mut(cell_id).unwrap();
-            if !cell.water
+    pub fn assign_coastal_cells(graph: &mut Graph) -> Result<&mut Graph, MapGenError> {
+        for cell_id in graph.cell_ids() {
+            let cell = &graph[cell_id];
+            let is_coast = !cell.water
                 && !cell.ocean
-                && graph_clone
+                && graph
                     .get_cell_adjacent_cells(cell_id)
                     .iter()
-                    .any(|id| graph_clone.cells.get(id).unwrap().ocean)
-            {
+                    .any(|id| graph[*id].ocean);
+            if is_coast {
+                let cell = &mut graph[cell_id];
                 cell.coast = true;
                 cell.biome = Biome::Beach;
             }
-            drop(cell);
         }
-        return graph;
+        return Ok(graph);
     }
 }
@@ -0,0 +1,688 @@
+pub mod pipeline {
+    use std::time::{Duration, Instant};
+
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::{
+        config::config::GenerationConfig,
+        error::error::MapGenError,
+        graph2::{
+            graph2::{build_base_graph, Graph},
+            sphere::sphere::build_sphere_graph,
+        },
+        terrain2::{
+            report::report::{GenerationReport, StageTiming},
+            stage::stage::{default_stages, TerrainStage},
+        },
+        voronoi::voronoi::RelaxationReport,
+    };
+
+    /// The name `Pipeline` gives the base graph, which every stage runs on
+    pub const BASE_GRAPH: &str = "base_graph";
+
+    fn run_base_graph(
+        config: &GenerationConfig,
+        rng: &mut StdRng,
+    ) -> Result<(Graph, RelaxationReport, Duration), MapGenError> {
+        let start = Instant::now();
+        let (graph, relaxation) = if config.sphere {
+            build_sphere_graph(config, rng)?
+        } else {
+            build_base_graph(config, rng)?
+        };
+        return Ok((graph, relaxation, start.elapsed()));
+    }
+
+    fn run_stage(
+        stage: &dyn TerrainStage,
+        graph: &mut Graph,
+        config: &GenerationConfig,
+        rng: &mut StdRng,
+    ) -> Result<Duration, MapGenError> {
+        let start = Instant::now();
+        stage.run(graph, config, rng)?;
+        return Ok(start.elapsed());
+    }
+
+    fn validate(
+        stages: &[Box<dyn TerrainStage>],
+        config: &GenerationConfig,
+    ) -> Result<(), MapGenError> {
+        config.validate()?;
+        for stage in stages {
+            stage.validate(config)?;
+        }
+        return Ok(());
+    }
+
+    /// Runs the default stages in turn without keeping their outputs, see
+    /// `full_terrain_gen`
+    pub(crate) fn run_all(
+        config: &GenerationConfig,
+        seed: u64,
+    ) -> Result<(Graph, GenerationReport), MapGenError> {
+        let stages = default_stages(config);
+        validate(&stages, config)?;
+        let mut rng = StdRng::seed_from_u64(seed);
+        let (mut graph, relaxation, duration) = run_base_graph(config, &mut rng)?;
+        let mut timings = vec![StageTiming {
+            name: String::from(BASE_GRAPH),
+            duration,
+        }];
+        for stage in &stages {
+            timings.push(StageTiming {
+                name: String::from(stage.name()),
+                duration: run_stage(stage.as_ref(), &mut graph, config, &mut rng)?,
+            });
+        }
+        let report = GenerationReport::new(&graph, timings, relaxation);
+        return Ok((graph, report));
+    }
+
+    // how long a stage took and, if it is kept, a snapshot of the graph it
+    // produced with the rng as the stage left it, so the stages after it can
+    // be re-run exactly as they first ran
+    #[derive(Debug, Clone)]
+    struct StageOutput {
+        snapshot: Option<(Graph, StdRng)>,
+        duration: Duration,
+    }
+
+    // where a run started, the outputs from there on and the relaxation if
+    // the base graph was rebuilt
+    type Run = (usize, Vec<StageOutput>, Option<RelaxationReport>);
+
+    /// A generation run that can be re-run from any stage with a new config.
+    /// Re-running from a stage gives the same graph as running the whole
+    /// pipeline with the new config, as long as only parameters used by that
+    /// stage or later ones changed, e.g. `moisture_falloff` from `"biomes"`.
+    /// Changes to the base graph parameters, such as `cell_count`, need a
+    /// re-run from `BASE_GRAPH`.
+    ///
+    /// Only the finished graph is kept by default, so re-runs start again
+    /// from the voronoi. `with_snapshots` keeps the graph after each stage
+    /// from a chosen point on, trading memory for quicker re-runs.
+    ///
+    /// Stages are addressed by `TerrainStage::name`. Inserting, replacing or
+    /// removing one, or changing one of its settings, re-runs the pipeline
+    /// from that point, so the outputs always match the current stages.
+    #[derive(Debug)]
+    pub struct Pipeline {
+        config: GenerationConfig,
+        seed: u64,
+        relaxation: RelaxationReport,
+        stages: Vec<Box<dyn TerrainStage>>,
+        // the earliest stage re-runs are expected from
+        snapshots_from: String,
+        // the base graph, then each stage in order. The last always has a
+        // snapshot, the finished map
+        outputs: Vec<StageOutput>,
+    }
+
+    impl Pipeline {
+        /// Runs the stages of `full_terrain_gen`, failing like it does
+        pub fn new(config: GenerationConfig, seed: u64) -> Result<Pipeline, MapGenError> {
+            let stages = default_stages(&config);
+            return Pipeline::with_stages(config, seed, stages);
+        }
+
+        /// Runs the base graph and then `stages`, which must have unique names
+        pub fn with_stages(
+            config: GenerationConfig,
+            seed: u64,
+            stages: Vec<Box<dyn TerrainStage>>,
+        ) -> Result<Pipeline, MapGenError> {
+            return Pipeline::with_snapshots(config, seed, stages, BASE_GRAPH);
+        }
+
+        /// As `with_stages`, also keeping the graph from the stage before
+        /// `rerun_from` onwards, so re-running from it or any later stage
+        /// skips the stages before it. Should `rerun_from` be removed later,
+        /// no snapshots are kept.
+        pub fn with_snapshots(
+            config: GenerationConfig,
+            seed: u64,
+            stages: Vec<Box<dyn TerrainStage>>,
+            rerun_from: &str,
+        ) -> Result<Pipeline, MapGenError> {
+            for (i, stage) in stages.iter().enumerate() {
+                let name = stage.name();
+                if name == BASE_GRAPH || stages[..i].iter().any(|s| s.name() == name) {
+                    return Err(duplicate_name(name));
+                }
+            }
+            let mut pipeline = Pipeline {
+                config: config.clone(),
+                seed,
+                relaxation: RelaxationReport::default(),
+                stages,
+                snapshots_from: String::from(rerun_from),
+                outputs: Vec::new(),
+            };
+            pipeline.position(rerun_from)?;
+            let run = pipeline.run_from(0, &config)?;
+            pipeline.commit(run);
+            return Ok(pipeline);
+        }
+
+        pub fn config(&self) -> &GenerationConfig {
+            return &self.config;
+        }
+
+        pub fn seed(&self) -> u64 {
+            return self.seed;
+        }
+
+        /// The base graph name followed by each stage's, in the order they run
+        pub fn stage_names(&self) -> Vec<&str> {
+            let mut names = vec![BASE_GRAPH];
+            names.extend(self.stages.iter().map(|s| s.name()));
+            return names;
+        }
+
+        /// The stage called `name`, whose settings can be read with
+        /// `TerrainStage::settings`
+        pub fn stage(&self, name: &str) -> Option<&dyn TerrainStage> {
+            let position = self.stage_position(name).ok()?;
+            return Some(self.stages[position - 1].as_ref());
+        }
+
+        /// The finished map
+        pub fn graph(&self) -> &Graph {
+            return &self.last_snapshot().0;
+        }
+
+        /// The graph as it was left by the stage called `name`, if a snapshot
+        /// of it was kept, see `with_snapshots`. The last stage's is always kept.
+        pub fn stage_graph(&self, name: &str) -> Option<&Graph> {
+            let position = self.position(name).ok()?;
+            return self.outputs[position]
+                .snapshot
+                .as_ref()
+                .map(|(graph, _rng)| graph);
+        }
+
+        /// Timings of the latest run of each stage, with statistics of the
+        /// finished map
+        pub fn report(&self) -> GenerationReport {
+            let timings = self
+                .stage_names()
+                .iter()
+                .zip(&self.outputs)
+                .map(|(name, output)| StageTiming {
+                    name: String::from(*name),
+                    duration: output.duration,
+                })
+                .collect();
+            return GenerationReport::new(self.graph(), timings, self.relaxation.clone());
+        }
+
+        pub fn into_graph(mut self) -> Graph {
+            return self
+                .outputs
+                .pop()
+                .and_then(|output| output.snapshot)
+                .expect("the finished map is always kept")
+                .0;
+        }
+
+        /// Re-runs the stage called `name` and every stage after it with
+        /// `config`, which every stage takes its settings from again, see
+        /// `TerrainStage::configure`. Stages before it are only re-run when
+        /// no snapshot from them was kept. On error the pipeline is left as
+        /// it was.
+        pub fn rerun_from(
+            &mut self,
+            name: &str,
+            config: GenerationConfig,
+        ) -> Result<&Graph, MapGenError> {
+            let position = self.position(name)?;
+            let previous = self.stage_settings();
+            for stage in self.stages.iter_mut() {
+                stage.configure(&config);
+            }
+            return match self.run_from(position, &config) {
+                Ok(run) => {
+                    self.config = config;
+                    Ok(self.commit(run))
+                }
+                Err(e) => {
+                    self.restore_settings(previous);
+                    Err(e)
+                }
+            };
+        }
+
+        /// Changes the setting `key` of the stage called `name`, see
+        /// `TerrainStage::set_setting`, and runs the pipeline on from that
+        /// stage. The pipeline's config is left as it was.
+        pub fn set_stage_setting(
+            &mut self,
+            name: &str,
+            key: &str,
+            value: &str,
+        ) -> Result<&Graph, MapGenError> {
+            let position = self.stage_position(name)?;
+            let previous = self.stage_settings();
+            self.stages[position - 1].set_setting(key, value)?;
+            return match self.run_from(position, &self.config) {
+                Ok(run) => Ok(self.commit(run)),
+                Err(e) => {
+                    self.restore_settings(previous);
+                    Err(e)
+                }
+            };
+        }
+
+        /// Adds `stage` straight after the stage called `after`, which may be
+        /// `BASE_GRAPH`, and runs the pipeline on from it
+        pub fn insert_stage(
+            &mut self,
+            after: &str,
+            stage: Box<dyn TerrainStage>,
+        ) -> Result<&Graph, MapGenError> {
+            if self.position(stage.name()).is_ok() {
+                return Err(duplicate_name(stage.name()));
+            }
+            let position = self.position(after)? + 1;
+            self.stages.insert(position - 1, stage);
+            return match self.run_from(position, &self.config) {
+                Ok(run) => Ok(self.commit(run)),
+                Err(e) => {
+                    self.stages.remove(position - 1);
+                    Err(e)
+                }
+            };
+        }
+
+        /// Swaps the stage called `name` for `stage` and runs the pipeline on
+        /// from it, returning the stage it replaced
+        pub fn replace_stage(
+            &mut self,
+            name: &str,
+            stage: Box<dyn TerrainStage>,
+        ) -> Result<Box<dyn TerrainStage>, MapGenError> {
+            let position = self.stage_position(name)?;
+            if stage.name() != name && self.position(stage.name()).is_ok() {
+                return Err(duplicate_name(stage.name()));
+            }
+            let replaced = std::mem::replace(&mut self.stages[position - 1], stage);
+            return match self.run_from(position, &self.config) {
+                Ok(run) => {
+                    self.commit(run);
+                    Ok(replaced)
+                }
+                Err(e) => {
+                    self.stages[position - 1] = replaced;
+                    Err(e)
+                }
+            };
+        }
+
+        /// Takes out the stage called `name` and runs the pipeline on from
+        /// where it was, returning the removed stage
+        pub fn remove_stage(&mut self, name: &str) -> Result<Box<dyn TerrainStage>, MapGenError> {
+            let position = self.stage_position(name)?;
+            let removed = self.stages.remove(position - 1);
+            return match self.run_from(position, &self.config) {
+                Ok(run) => {
+                    self.commit(run);
+                    Ok(removed)
+                }
+                Err(e) => {
+                    self.stages.insert(position - 1, removed);
+                    Err(e)
+                }
+            };
+        }
+
+        // index into `outputs` of the named stage
+        fn position(&self, name: &str) -> Result<usize, MapGenError> {
+            return self
+                .stage_names()
+                .iter()
+                .position(|n| *n == name)
+                .ok_or_else(|| MapGenError::InvalidConfig(format!("no stage named `{}`", name)));
+        }
+
+        // as `position`, but the base graph can't be replaced or removed
+        fn stage_position(&self, name: &str) -> Result<usize, MapGenError> {
+            if name == BASE_GRAPH {
+                return Err(MapGenError::InvalidConfig(String::from(
+                    "the base graph is not a stage",
+                )));
+            }
+            return self.position(name);
+        }
+
+        // every stage's settings, to put back should a re-run fail
+        fn stage_settings(&self) -> Vec<Vec<(String, String)>> {
+            return self
+                .stages
+                .iter()
+                .map(|stage| {
+                    return stage
+                        .settings()
+                        .into_iter()
+                        .map(|(key, value)| (String::from(key), value))
+                        .collect();
+                })
+                .collect();
+        }
+
+        fn restore_settings(&mut self, settings: Vec<Vec<(String, String)>>) {
+            for (stage, settings) in self.stages.iter_mut().zip(settings) {
+                for (key, value) in settings {
+                    stage
+                        .set_setting(&key, &value)
+                        .expect("a stage accepts its own settings back");
+                }
+            }
+        }
+
+        fn last_snapshot(&self) -> &(Graph, StdRng) {
+            return self
+                .outputs
+                .last()
+                .and_then(|output| output.snapshot.as_ref())
+                .expect("the finished map is always kept");
+        }
+
+        // whether the graph after the stage at `index` is needed to re-run
+        // from `snapshots_from` or a later stage
+        fn keeps_snapshot(&self, index: usize) -> bool {
+            return match self.position(&self.snapshots_from) {
+                Ok(position) => position > 0 && index + 1 >= position,
+                Err(_) => false,
+            };
+        }
+
+        // runs the stages from `position` onwards, starting from the latest
+        // snapshot before it or from the voronoi if there is none
+        fn run_from(&self, position: usize, config: &GenerationConfig) -> Result<Run, MapGenError> {
+            validate(&self.stages, config)?;
+            let snapshot = (0..position)
+                .rev()
+                .find_map(|i| Some((i, self.outputs[i].snapshot.as_ref()?)));
+            let mut outputs: Vec<StageOutput> = Vec::new();
+            let mut relaxation: Option<RelaxationReport> = None;
+            let (start, mut graph, mut rng) = match snapshot {
+                Some((i, (graph, rng))) => (i + 1, graph.clone(), rng.clone()),
+                None => {
+                    let mut rng = StdRng::seed_from_u64(self.seed);
+                    let (graph, base_relaxation, duration) = run_base_graph(config, &mut rng)?;
+                    relaxation = Some(base_relaxation);
+                    outputs.push(StageOutput {
+                        snapshot: self.keeps_snapshot(0).then(|| (graph.clone(), rng.clone())),
+                        duration,
+                    });
+                    (0, graph, rng)
+                }
+            };
+            let last = self.stages.len();
+            for (i, stage) in self.stages.iter().enumerate().skip(start.max(1) - 1) {
+                let duration = run_stage(stage.as_ref(), &mut graph, config, &mut rng)?;
+                let keep = i + 1 < last && self.keeps_snapshot(i + 1);
+                outputs.push(StageOutput {
+                    snapshot: keep.then(|| (graph.clone(), rng.clone())),
+                    duration,
+                });
+            }
+            // the finished map is moved in rather than cloned
+            if let Some(last) = outputs.last_mut() {
+                last.snapshot = Some((graph, rng));
+            }
+            return Ok((start, outputs, relaxation));
+        }
+
+        fn commit(&mut self, (start, outputs, relaxation): Run) -> &Graph {
+            if let Some(relaxation) = relaxation {
+                self.relaxation = relaxation;
+            }
+            self.outputs.truncate(start);
+            self.outputs.extend(outputs);
+            // a run may have started from the old finished map, or from a
+            // snapshot whose stage has since been removed
+            let keep: Vec<bool> = (0..self.stages.len())
+                .map(|i| self.keeps_snapshot(i))
+                .collect();
+            for (output, keep) in self.outputs.iter_mut().zip(keep) {
+                if !keep {
+                    output.snapshot = None;
+                }
+            }
+            return self.graph();
+        }
+    }
+
+    fn duplicate_name(name: &str) -> MapGenError {
+        return MapGenError::InvalidConfig(format!("a stage named `{}` already exists", name));
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::{
+            graph2::graph2::Biome,
+            terrain2::{stage::stage::BiomeStage, terrain2::full_terrain_gen},
+        };
+
+        fn test_config() -> GenerationConfig {
+            return GenerationConfig {
+                cell_count: 500,
+                ..Default::default()
+            };
+        }
+
+        #[test]
+        fn rerun_matches_full_generation_test() {
+            let config = test_config();
+            let mut pipeline =
+                Pipeline::with_snapshots(config.clone(), 9, default_stages(&config), "island")
+                    .unwrap();
+            assert_eq!(pipeline.graph(), &full_terrain_gen(&config, 9).unwrap().0);
+            let base = pipeline.stage_graph(BASE_GRAPH).unwrap().clone();
+
+            let drier = GenerationConfig {
+                moisture_falloff: 0.3,
+                ..config.clone()
+            };
+            pipeline.rerun_from("biomes", drier.clone()).unwrap();
+            assert_eq!(pipeline.graph(), &full_terrain_gen(&drier, 9).unwrap().0);
+
+            let wetter = GenerationConfig {
+                water_coverage_modifier: 1.5,
+                ..drier
+            };
+            pipeline.rerun_from("island", wetter.clone()).unwrap();
+            assert_eq!(pipeline.graph(), &full_terrain_gen(&wetter, 9).unwrap().0);
+            assert_eq!(pipeline.stage_graph(BASE_GRAPH).unwrap(), &base);
+
+            let invalid = GenerationConfig {
+                moisture_falloff: 0.0,
+                ..wetter.clone()
+            };
+            assert!(pipeline.rerun_from("biomes", invalid).is_err());
+            assert!(pipeline.rerun_from("glaciers", wetter.clone()).is_err());
+            assert_eq!(pipeline.config(), &wetter);
+            assert_eq!(pipeline.graph(), &full_terrain_gen(&wetter, 9).unwrap().0);
+        }
+
+        #[test]
+        fn snapshots_are_opt_in_test() {
+            let config = test_config();
+            let mut pipeline = Pipeline::new(config.clone(), 9).unwrap();
+            assert!(pipeline.stage_graph(BASE_GRAPH).is_none());
+            assert!(pipeline.stage_graph("rivers").is_none());
+            assert_eq!(
+                pipeline.stage_graph("edge_divisions"),
+                Some(pipeline.graph())
+            );
+
+            // without a snapshot the re-run starts from the voronoi
+            let drier = GenerationConfig {
+                moisture_falloff: 0.3,
+                ..config.clone()
+            };
+            pipeline.rerun_from("biomes", drier.clone()).unwrap();
+            assert_eq!(pipeline.graph(), &full_terrain_gen(&drier, 9).unwrap().0);
+
+            let stages = default_stages(&config);
+            let pipeline = Pipeline::with_snapshots(config, 9, stages, "biomes").unwrap();
+            assert!(pipeline.stage_graph("elevation").is_none());
+            assert!(pipeline.stage_graph("rivers").is_some());
+            assert!(pipeline.stage_graph("biomes").is_some());
+            assert!(Pipeline::with_snapshots(
+                test_config(),
+                9,
+                default_stages(&test_config()),
+                "glaciers"
+            )
+            .is_err());
+        }
+
+        #[test]
+        fn stage_settings_test() {
+            let config = test_config();
+            let mut pipeline = Pipeline::new(config.clone(), 9).unwrap();
+            assert_eq!(
+                pipeline.stage("biomes").unwrap().settings(),
+                vec![
+                    ("moisture_falloff", config.moisture_falloff.to_string()),
+                    (
+                        "moisture_elevation_weight",
+                        config.moisture_elevation_weight.to_string()
+                    ),
+                ]
+            );
+            assert!(pipeline.stage("ocean").unwrap().settings().is_empty());
+            assert!(pipeline.stage(BASE_GRAPH).is_none());
+
+            pipeline
+                .set_stage_setting("biomes", "moisture_falloff", "0.3")
+                .unwrap();
+            let drier = GenerationConfig {
+                moisture_falloff: 0.3,
+                ..config.clone()
+            };
+            assert_eq!(pipeline.graph(), &full_terrain_gen(&drier, 9).unwrap().0);
+            assert_eq!(pipeline.config(), &config);
+
+            // another stage's setting, an unreadable value or one that fails
+            // validation leave the pipeline as it was
+            let before = pipeline.graph().clone();
+            for (key, value) in [
+                ("noise_scale", "2"),
+                ("moisture_falloff", "damp"),
+                ("moisture_falloff", "0"),
+            ] {
+                assert!(pipeline.set_stage_setting("biomes", key, value).is_err());
+            }
+            assert_eq!(pipeline.stage("biomes").unwrap().settings()[0].1, "0.3");
+            assert_eq!(pipeline.graph(), &before);
+
+            // a new config replaces every stage's settings
+            pipeline.rerun_from("biomes", config.clone()).unwrap();
+            assert_eq!(pipeline.graph(), &full_terrain_gen(&config, 9).unwrap().0);
+        }
+
+        // lifts every land corner, standing in for a caller's own stage
+        #[derive(Debug)]
+        struct Volcano {
+            height: f32,
+        }
+
+        impl TerrainStage for Volcano {
+            fn name(&self) -> &str {
+                return "volcano";
+            }
+
+            fn run(
+                &self,
+                graph: &mut Graph,
+                _config: &GenerationConfig,
+                _rng: &mut StdRng,
+            ) -> Result<(), MapGenError> {
+                for corner in graph.corners.iter_mut() {
+                    if corner.elevation > 0.0 {
+                        corner.elevation += self.height;
+                    }
+                }
+                return Ok(());
+            }
+        }
+
+        #[derive(Debug)]
+        struct AllGrassland;
+
+        impl TerrainStage for AllGrassland {
+            fn name(&self) -> &str {
+                return "grassland";
+            }
+
+            fn run(
+                &self,
+                graph: &mut Graph,
+                _config: &GenerationConfig,
+                _rng: &mut StdRng,
+            ) -> Result<(), MapGenError> {
+                for cell in graph.cells.iter_mut().filter(|c| !c.water) {
+                    cell.biome = Biome::Grassland;
+                }
+                return Ok(());
+            }
+        }
+
+        #[test]
+        fn custom_stages_test() {
+            let mut pipeline = Pipeline::new(test_config(), 4).unwrap();
+            let max_elevation = |graph: &Graph| {
+                return graph
+                    .corners
+                    .iter()
+                    .map(|c| c.elevation)
+                    .fold(f32::MIN, f32::max);
+            };
+            let before = max_elevation(pipeline.graph());
+
+            pipeline
+                .insert_stage("elevation", Box::new(Volcano { height: 2.0 }))
+                .unwrap();
+            assert_eq!(
+                pipeline.stage_names()[..6],
+                [
+                    BASE_GRAPH,
+                    "island",
+                    "ocean",
+                    "coastal",
+                    "elevation",
+                    "volcano"
+                ]
+            );
+            assert_eq!(max_elevation(pipeline.graph()), before + 2.0);
+            assert!(pipeline
+                .insert_stage(BASE_GRAPH, Box::new(Volcano { height: 1.0 }))
+                .is_err());
+
+            let replaced = pipeline
+                .replace_stage("biomes", Box::new(AllGrassland))
+                .unwrap();
+            assert_eq!(replaced.name(), "biomes");
+            assert!(pipeline
+                .graph()
+                .cells
+                .iter()
+                .all(|c| c.water || c.biome == Biome::Grassland));
+
+            pipeline.remove_stage("volcano").unwrap();
+            pipeline
+                .replace_stage("grassland", Box::new(BiomeStage::new(&test_config())))
+                .unwrap();
+            assert_eq!(
+                pipeline.graph(),
+                &full_terrain_gen(&test_config(), 4).unwrap().0
+            );
+            assert!(pipeline.remove_stage(BASE_GRAPH).is_err());
+        }
+    }
+}
@@ -0,0 +1,147 @@
+pub mod report {
+    use std::time::Duration;
+
+    use crate::{
+        export::export::river_paths,
+        graph2::graph2::{Biome, Graph},
+        voronoi::voronoi::RelaxationReport,
+    };
+
+    /// How long one stage of generation took
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct StageTiming {
+        pub name: String,
+        pub duration: Duration,
+    }
+
+    /// Timings and statistics of a generated map, for tracking generation
+    /// speed and map quality across versions and configs
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct GenerationReport {
+        /// The base graph, then every stage in the order they ran
+        pub stages: Vec<StageTiming>,
+        pub relaxation: RelaxationReport,
+        pub cell_count: usize,
+        pub edge_count: usize,
+        pub corner_count: usize,
+        /// Fraction of cells that are land
+        pub land_ratio: f64,
+        /// Fraction of cells that are ocean
+        pub ocean_ratio: f64,
+        /// Fraction of cells that are lakes, water cells cut off from the ocean
+        pub lake_ratio: f64,
+        /// Number of river runs, see `river_paths`. A tributary is its own run
+        pub river_count: usize,
+        /// Length of every river edge along its midpoints, in map units
+        pub river_length: f64,
+        /// Cells of each biome, in the order of `Biome::ALL`
+        pub biome_histogram: Vec<(Biome, usize)>,
+    }
+
+    impl GenerationReport {
+        pub(crate) fn new(
+            graph: &Graph,
+            stages: Vec<StageTiming>,
+            relaxation: RelaxationReport,
+        ) -> GenerationReport {
+            let cell_count = graph.cells.len();
+            let ratio = |count: usize| count as f64 / cell_count.max(1) as f64;
+            let rivers = river_paths(graph);
+            let river_length: f64 = rivers
+                .iter()
+                .flat_map(|river| river.points.windows(2))
+                .map(|pair| {
+                    let (dx, dy) = (pair[1].0 - pair[0].0, pair[1].1 - pair[0].1);
+                    return (dx as f64).hypot(dy as f64);
+                })
+                .sum();
+            let biome_histogram = Biome::ALL
+                .iter()
+                .map(|biome| {
+                    let count = graph.cells.iter().filter(|c| c.biome == *biome).count();
+                    return (biome.clone(), count);
+                })
+                .collect();
+            return GenerationReport {
+                stages,
+                relaxation,
+                cell_count,
+                edge_count: graph.edges.len(),
+                corner_count: graph.corners.len(),
+                land_ratio: ratio(graph.cells.iter().filter(|c| !c.water).count()),
+                ocean_ratio: ratio(graph.cells.iter().filter(|c| c.ocean).count()),
+                lake_ratio: ratio(graph.cells.iter().filter(|c| c.water && !c.ocean).count()),
+                river_count: rivers.len(),
+                river_length,
+                biome_histogram,
+            };
+        }
+
+        /// Time spent across every stage
+        pub fn total_duration(&self) -> Duration {
+            return self.stages.iter().map(|s| s.duration).sum();
+        }
+
+        /// Fraction of cells that are ocean or lake
+        pub fn water_ratio(&self) -> f64 {
+            return self.ocean_ratio + self.lake_ratio;
+        }
+
+        /// How long the named stage took, if it ran
+        pub fn stage_duration(&self, name: &str) -> Option<Duration> {
+            return self
+                .stages
+                .iter()
+                .find(|s| s.name == name)
+                .map(|s| s.duration);
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use crate::{
+            config::config::GenerationConfig,
+            terrain2::{pipeline::pipeline::Pipeline, terrain2::full_terrain_gen},
+        };
+
+        #[test]
+        fn report_summarises_the_map_test() {
+            let config = GenerationConfig {
+                cell_count: 500,
+                ..Default::default()
+            };
+            let (graph, report) = full_terrain_gen(&config, 12).unwrap();
+            let names: Vec<&str> = report.stages.iter().map(|s| s.name.as_str()).collect();
+            assert_eq!(
+                names,
+                [
+                    "base_graph",
+                    "island",
+                    "ocean",
+                    "coastal",
+                    "elevation",
+                    "rivers",
+                    "biomes",
+                    "edge_divisions"
+                ]
+            );
+            assert!(report.stage_duration("rivers").is_some());
+            assert_eq!(report.cell_count, 500);
+            assert_eq!(report.edge_count, graph.edges.len());
+            assert_eq!(report.corner_count, graph.corners.len());
+            assert!((report.land_ratio + report.water_ratio() - 1.0).abs() < 1e-9);
+            assert!(report.land_ratio > 0.0 && report.ocean_ratio > 0.0);
+            assert!(report.river_count > 0 && report.river_length > 0.0);
+            assert!(report.relaxation.iterations > 0);
+            let histogram_total: usize = report.biome_histogram.iter().map(|(_, n)| n).sum();
+            assert_eq!(histogram_total, 500);
+
+            // a pipeline's report differs only in its timings
+            let pipeline = Pipeline::new(config, 12).unwrap();
+            let mut from_pipeline = pipeline.report();
+            assert_eq!(from_pipeline.stages.len(), report.stages.len());
+            from_pipeline.stages = report.stages.clone();
+            assert_eq!(from_pipeline, report);
+        }
+    }
+}
@@ -1,102 +1,161 @@
 pub mod rivers2 {
-    use std::collections::HashSet;
+    use std::{cmp::Ordering, collections::HashSet};
 
-    use uuid::Uuid;
+    use rand::{seq::SliceRandom, Rng};
 
     use crate::{
-        graph2::graph2::{Cell, Corner, Edge, Graph},
-        helpers::helpers::{corner_distance, corner_distance2},
+        config::config::GenerationConfig,
+        error::error::MapGenError,
+        graph2::graph2::{CellId, Corner, CornerId, EdgeId, Graph},
     };
 
-    const PEAK_RAINWATER_COLLECTION_RATIO: f32 = 0.25;
-    const PEAK_ELEV_MAX: f32 = 0.9;
-    const PEAK_ELEV_MIN: f32 = 0.75;
-
-    const MID_RAINWATER_COLLECTION_RATION: f32 = 0.03;
-    const MID_ELEV_MAX: f32 = 0.75;
-    const MID_ELEV_MIN: f32 = 0.33;
-
-    fn get_edges_with_elevations_between(graph: &Graph, min: f32, max: f32) -> Vec<(&Uuid, &Edge)> {
-        let mut edge_ids: Vec<&Uuid> = graph.edges.keys().collect();
+    // returns each edge along with its river volume before this stage ran, so
+    // the edges can be walked while the graph is being written to
+    fn get_edges_with_elevations_between(graph: &Graph, min: f32, max: f32) -> Vec<(EdgeId, f32)> {
+        let mut edge_ids: Vec<EdgeId> = graph.edge_ids().collect();
         edge_ids.retain(|id| {
-            let elev = graph.get_edge_elevation(id);
+            let elev = graph.get_edge_elevation(*id);
             return elev > min && elev < max;
         });
-        return edge_ids
-            .iter()
-            .map(|id| (id.clone(), graph.edges.get(id).unwrap()))
-            .collect();
+        return edge_ids.iter().map(|id| (*id, graph[*id].river)).collect();
     }
 
     fn corner_is_river_end(graph: &Graph, corner: &Corner) -> bool {
-        let corner_edges = corner.edges.iter().map(|id| graph.edges.get(&id).unwrap());
-        let cell_ids: Vec<Vec<Uuid>> = corner_edges.map(|edge| edge.cells.clone()).collect();
+        let corner_edges = corner.edges.iter().map(|id| &graph[*id]);
+        let cell_ids: Vec<Vec<CellId>> = corner_edges.map(|edge| edge.cells.clone()).collect();
         return cell_ids
             .concat()
             .iter()
-            .any(|cell_id| graph.cells.get(cell_id).unwrap().water);
+            .any(|cell_id| graph[*cell_id].water);
     }
 
-    pub fn create_rivers(graph: &mut Graph) -> &mut Graph {
-        let graph_clone = graph.clone();
-
+    // picks a random share of the edges in each elevation band for rivers to
+    // start from, so sources are spread over the whole map rather than
+    // bunching up at the lowest ids
+    fn choose_starting_edges(
+        graph: &Graph,
+        config: &GenerationConfig,
+        rng: &mut impl Rng,
+    ) -> Vec<(EdgeId, f32)> {
         let peak_starting_edges =
-            get_edges_with_elevations_between(&graph_clone, PEAK_ELEV_MIN, PEAK_ELEV_MAX);
+            get_edges_with_elevations_between(graph, config.peak_elev_min, config.peak_elev_max);
         let mid_starting_edges =
-            get_edges_with_elevations_between(&graph_clone, MID_ELEV_MIN, MID_ELEV_MAX);
+            get_edges_with_elevations_between(graph, config.mid_elev_min, config.mid_elev_max);
 
         let peak_starting_edge_count = ((peak_starting_edges.len()) as f32
-            * PEAK_RAINWATER_COLLECTION_RATIO)
+            * config.peak_rainwater_collection_ratio)
             .max(1.0) as usize;
 
-        let mid_starting_edge_count =
-            ((mid_starting_edges.len()) as f32 * MID_RAINWATER_COLLECTION_RATION).max(1.0) as usize;
+        let mid_starting_edge_count = ((mid_starting_edges.len()) as f32
+            * config.mid_rainwater_collection_ratio)
+            .max(1.0) as usize;
 
-        let peak_edges: Vec<&(&Uuid, &Edge)> = peak_starting_edges
-            .iter()
-            .take(peak_starting_edge_count)
-            .collect();
-        let mid_edges: Vec<&(&Uuid, &Edge)> = mid_starting_edges
-            .iter()
-            .take(mid_starting_edge_count)
+        let mut output: Vec<(EdgeId, f32)> = peak_starting_edges
+            .choose_multiple(rng, peak_starting_edge_count)
+            .copied()
             .collect();
+        output.extend(
+            mid_starting_edges
+                .choose_multiple(rng, mid_starting_edge_count)
+                .copied(),
+        );
+        return output;
+    }
 
-        let starting_edges: Vec<&(&Uuid, &Edge)> = vec![peak_edges, mid_edges].concat();
-
-        for (edge_id, edge) in starting_edges {
-            let distance = corner_distance2(
-                graph_clone.corners.get(&edge.corners.0).unwrap(),
-                graph_clone.corners.get(&edge.corners.1).unwrap(),
-            );
-            let working_edge = *edge;
-            let mut working_edge_id = *edge_id.clone();
-            let mut new_volume = working_edge.river;
-            let mut visited_corners: HashSet<Uuid> = HashSet::new();
+    pub fn create_rivers<'a>(
+        graph: &'a mut Graph,
+        config: &GenerationConfig,
+        rng: &mut impl Rng,
+    ) -> Result<&'a mut Graph, MapGenError> {
+        let starting_edges = choose_starting_edges(graph, config, rng);
+
+        for (edge_id, starting_volume) in starting_edges {
+            let (c_1, c_2) = graph[edge_id].corners;
+            let distance = graph.pos_distance(graph[c_1].pos, graph[c_2].pos);
+            let mut working_edge_id = edge_id;
+            let mut new_volume = starting_volume;
+            let mut visited_corners: HashSet<CornerId> = HashSet::new();
             loop {
                 new_volume += distance;
-                let edge_mut = graph.edges.get_mut(&working_edge_id).unwrap();
-                edge_mut.river = edge_mut.river + new_volume;
+                graph[working_edge_id].river += new_volume;
 
                 let (down_corner_id, down_corner) =
-                    graph.get_edge_downwards_corner(&working_edge_id);
+                    graph.get_edge_downwards_corner(working_edge_id);
 
                 if visited_corners.contains(&down_corner_id)
-                    || corner_is_river_end(&graph_clone, down_corner)
+                    || corner_is_river_end(graph, down_corner)
                 {
                     break;
                 }
-                visited_corners.insert(down_corner_id.clone());
-                let mut down_corner_edges: Vec<Uuid> = down_corner.edges.clone();
+                visited_corners.insert(down_corner_id);
+                let mut down_corner_edges: Vec<EdgeId> = down_corner.edges.clone();
+                if let Some(id) = down_corner_edges
+                    .iter()
+                    .find(|id| !graph.get_edge_elevation(**id).is_finite())
+                {
+                    return Err(MapGenError::NonFiniteElevation(*id));
+                }
                 down_corner_edges.sort_by(|a_id, b_id| {
-                    let a_elev = graph.get_edge_elevation(a_id);
-                    let b_elev = graph.get_edge_elevation(b_id);
-                    return a_elev.partial_cmp(&b_elev).unwrap();
+                    let a_elev = graph.get_edge_elevation(*a_id);
+                    let b_elev = graph.get_edge_elevation(*b_id);
+                    return a_elev.partial_cmp(&b_elev).unwrap_or(Ordering::Equal);
                 });
-                let next_edge = down_corner_edges.first().unwrap();
-                working_edge_id = (*next_edge).clone();
+                // a corner is always reached through at least one of its own edges
+                working_edge_id = down_corner_edges[0];
             }
         }
 
-        return graph;
+        return Ok(graph);
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        use super::*;
+        use crate::{
+            terrain2::{pipeline::pipeline::Pipeline, stage::stage::default_stages},
+            voronoi::voronoi::PointDistribution,
+        };
+
+        #[test]
+        fn starting_edges_spread_over_map_test() {
+            let config = GenerationConfig {
+                cell_count: 2000,
+                point_distribution: PointDistribution::HexGrid,
+                ..Default::default()
+            };
+            let pipeline =
+                Pipeline::with_snapshots(config.clone(), 5, default_stages(&config), "rivers")
+                    .unwrap();
+            let graph = pipeline.stage_graph("elevation").unwrap();
+            let edge_y = |id: EdgeId| {
+                let (c_1, c_2) = graph[id].corners;
+                return (graph[c_1].pos.1 + graph[c_2].pos.1) / 2.0;
+            };
+            let y_span = |ys: Vec<f32>| {
+                let min = ys.iter().copied().fold(f32::INFINITY, f32::min);
+                let max = ys.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+                return max - min;
+            };
+
+            let candidates: Vec<f32> = get_edges_with_elevations_between(
+                graph,
+                config.peak_elev_min,
+                config.peak_elev_max,
+            )
+            .iter()
+            .map(|(id, _river)| edge_y(*id))
+            .collect();
+            let starting_edges =
+                choose_starting_edges(graph, &config, &mut StdRng::seed_from_u64(5));
+            let sources: Vec<f32> = starting_edges
+                .iter()
+                .map(|(id, _river)| edge_y(*id))
+                .collect();
+            // hex grid ids run row by row, so the lowest ids would all sit in a
+            // thin band at the bottom of the map
+            assert!(y_span(sources) > y_span(candidates) * 0.5);
+        }
     }
 }
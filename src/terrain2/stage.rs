@@ -0,0 +1,440 @@
+pub mod stage {
+    use std::fmt;
+
+    use rand::rngs::StdRng;
+
+    use crate::{
+        config::config::GenerationConfig,
+        error::error::MapGenError,
+        graph2::graph2::Graph,
+        terrain2::{
+            biome::biome::assign_biomes,
+            edge_detail::edge_detail::add_edge_divisions,
+            elevation::elevation2::assign_land_elevation,
+            island::island2::{assign_coastal_cells, assign_ocean_cells, run_island_gen},
+            rivers::rivers2::create_rivers,
+        },
+    };
+
+    /// One step of generation, run on the graph after the base graph is
+    /// built. A stage keeps its own settings, which a `Pipeline` can read
+    /// and change through `settings` and `set_setting` without knowing the
+    /// stage's type. The built-in stages copy theirs from the
+    /// `GenerationConfig` fields of the same names. Any randomness must come
+    /// from `rng` for maps to stay reproducible from their seed.
+    pub trait TerrainStage: fmt::Debug + Send + Sync {
+        /// Identifies the stage within a `Pipeline`, so must be unique there
+        fn name(&self) -> &str;
+
+        /// Rejects settings that would fail part way through `run`. Called
+        /// before any stage runs
+        fn validate(&self, _config: &GenerationConfig) -> Result<(), MapGenError> {
+            return Ok(());
+        }
+
+        /// Takes the stage's settings from `config`, called when a
+        /// `Pipeline` is re-run with a new config
+        fn configure(&mut self, _config: &GenerationConfig) {}
+
+        /// The stage's settings as `(name, value)` pairs
+        fn settings(&self) -> Vec<(&str, String)> {
+            return Vec::new();
+        }
+
+        /// Changes the setting called `key`, parsing `value` as a config
+        /// file would
+        fn set_setting(&mut self, key: &str, _value: &str) -> Result<(), MapGenError> {
+            return Err(unknown_setting(self.name(), key));
+        }
+
+        fn run(
+            &self,
+            graph: &mut Graph,
+            config: &GenerationConfig,
+            rng: &mut StdRng,
+        ) -> Result<(), MapGenError>;
+    }
+
+    fn unknown_setting(stage: &str, key: &str) -> MapGenError {
+        return MapGenError::InvalidConfig(format!("stage `{}` has no setting `{}`", stage, key));
+    }
+
+    // parses `value` into the config field `key` if it is one of the stage's
+    // settings, then takes the stage's settings back from the config
+    fn set_from_config(
+        stage: &mut dyn TerrainStage,
+        mut config: GenerationConfig,
+        key: &str,
+        value: &str,
+    ) -> Result<(), MapGenError> {
+        if !stage.settings().iter().any(|(name, _value)| *name == key) {
+            return Err(unknown_setting(stage.name(), key));
+        }
+        config
+            .set_field(key, value)
+            .map_err(MapGenError::InvalidConfig)?;
+        stage.configure(&config);
+        return Ok(());
+    }
+
+    /// Raises land from the sea with noise, see `run_island_gen`
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct IslandStage {
+        pub noise_scale: f32,
+        pub water_coverage_modifier: f64,
+    }
+
+    /// Floods water cells connected to the map border, see `assign_ocean_cells`
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct OceanStage;
+
+    /// Marks land cells touching the ocean, see `assign_coastal_cells`
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct CoastalStage;
+
+    /// Sets elevation by distance from the coast, see `assign_land_elevation`
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct ElevationStage;
+
+    /// Runs rainwater downhill from high edges, see `create_rivers`
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct RiverStage {
+        pub peak_rainwater_collection_ratio: f32,
+        pub peak_elev_max: f32,
+        pub peak_elev_min: f32,
+        pub mid_rainwater_collection_ratio: f32,
+        pub mid_elev_max: f32,
+        pub mid_elev_min: f32,
+    }
+
+    /// Spreads moisture from fresh water and picks biomes, see `assign_biomes`
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct BiomeStage {
+        pub moisture_falloff: f32,
+        pub moisture_elevation_weight: f32,
+    }
+
+    /// Subdivides edges into midpoints for drawing, see `add_edge_divisions`
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct EdgeDivisionStage {
+        pub edge_subdivisions: usize,
+    }
+
+    impl IslandStage {
+        pub fn new(config: &GenerationConfig) -> IslandStage {
+            return IslandStage {
+                noise_scale: config.noise_scale,
+                water_coverage_modifier: config.water_coverage_modifier,
+            };
+        }
+
+        // `config` with this stage's settings in place of its own
+        fn apply(&self, config: &GenerationConfig) -> GenerationConfig {
+            return GenerationConfig {
+                noise_scale: self.noise_scale,
+                water_coverage_modifier: self.water_coverage_modifier,
+                ..config.clone()
+            };
+        }
+    }
+
+    impl RiverStage {
+        pub fn new(config: &GenerationConfig) -> RiverStage {
+            return RiverStage {
+                peak_rainwater_collection_ratio: config.peak_rainwater_collection_ratio,
+                peak_elev_max: config.peak_elev_max,
+                peak_elev_min: config.peak_elev_min,
+                mid_rainwater_collection_ratio: config.mid_rainwater_collection_ratio,
+                mid_elev_max: config.mid_elev_max,
+                mid_elev_min: config.mid_elev_min,
+            };
+        }
+
+        fn apply(&self, config: &GenerationConfig) -> GenerationConfig {
+            return GenerationConfig {
+                peak_rainwater_collection_ratio: self.peak_rainwater_collection_ratio,
+                peak_elev_max: self.peak_elev_max,
+                peak_elev_min: self.peak_elev_min,
+                mid_rainwater_collection_ratio: self.mid_rainwater_collection_ratio,
+                mid_elev_max: self.mid_elev_max,
+                mid_elev_min: self.mid_elev_min,
+                ..config.clone()
+            };
+        }
+    }
+
+    impl BiomeStage {
+        pub fn new(config: &GenerationConfig) -> BiomeStage {
+            return BiomeStage {
+                moisture_falloff: config.moisture_falloff,
+                moisture_elevation_weight: config.moisture_elevation_weight,
+            };
+        }
+
+        fn apply(&self, config: &GenerationConfig) -> GenerationConfig {
+            return GenerationConfig {
+                moisture_falloff: self.moisture_falloff,
+                moisture_elevation_weight: self.moisture_elevation_weight,
+                ..config.clone()
+            };
+        }
+    }
+
+    impl EdgeDivisionStage {
+        pub fn new(config: &GenerationConfig) -> EdgeDivisionStage {
+            return EdgeDivisionStage {
+                edge_subdivisions: config.edge_subdivisions,
+            };
+        }
+
+        fn apply(&self, config: &GenerationConfig) -> GenerationConfig {
+            return GenerationConfig {
+                edge_subdivisions: self.edge_subdivisions,
+                ..config.clone()
+            };
+        }
+    }
+
+    impl Default for IslandStage {
+        fn default() -> Self {
+            return IslandStage::new(&GenerationConfig::default());
+        }
+    }
+
+    impl Default for RiverStage {
+        fn default() -> Self {
+            return RiverStage::new(&GenerationConfig::default());
+        }
+    }
+
+    impl Default for BiomeStage {
+        fn default() -> Self {
+            return BiomeStage::new(&GenerationConfig::default());
+        }
+    }
+
+    impl Default for EdgeDivisionStage {
+        fn default() -> Self {
+            return EdgeDivisionStage::new(&GenerationConfig::default());
+        }
+    }
+
+    impl TerrainStage for IslandStage {
+        fn name(&self) -> &str {
+            return "island";
+        }
+
+        fn validate(&self, config: &GenerationConfig) -> Result<(), MapGenError> {
+            return self.apply(config).validate();
+        }
+
+        fn configure(&mut self, config: &GenerationConfig) {
+            *self = IslandStage::new(config);
+        }
+
+        fn settings(&self) -> Vec<(&str, String)> {
+            return vec![
+                ("noise_scale", self.noise_scale.to_string()),
+                (
+                    "water_coverage_modifier",
+                    self.water_coverage_modifier.to_string(),
+                ),
+            ];
+        }
+
+        fn set_setting(&mut self, key: &str, value: &str) -> Result<(), MapGenError> {
+            let config = self.apply(&GenerationConfig::default());
+            return set_from_config(self, config, key, value);
+        }
+
+        fn run(
+            &self,
+            graph: &mut Graph,
+            config: &GenerationConfig,
+            rng: &mut StdRng,
+        ) -> Result<(), MapGenError> {
+            run_island_gen(graph, &self.apply(config), rng)?;
+            return Ok(());
+        }
+    }
+
+    impl TerrainStage for OceanStage {
+        fn name(&self) -> &str {
+            return "ocean";
+        }
+
+        fn run(
+            &self,
+            graph: &mut Graph,
+            _config: &GenerationConfig,
+            _rng: &mut StdRng,
+        ) -> Result<(), MapGenError> {
+            assign_ocean_cells(graph)?;
+            return Ok(());
+        }
+    }
+
+    impl TerrainStage for CoastalStage {
+        fn name(&self) -> &str {
+            return "coastal";
+        }
+
+        fn run(
+            &self,
+            graph: &mut Graph,
+            _config: &GenerationConfig,
+            _rng: &mut StdRng,
+        ) -> Result<(), MapGenError> {
+            assign_coastal_cells(graph)?;
+            return Ok(());
+        }
+    }
+
+    impl TerrainStage for ElevationStage {
+        fn name(&self) -> &str {
+            return "elevation";
+        }
+
+        fn run(
+            &self,
+            graph: &mut Graph,
+            _config: &GenerationConfig,
+            _rng: &mut StdRng,
+        ) -> Result<(), MapGenError> {
+            assign_land_elevation(graph)?;
+            return Ok(());
+        }
+    }
+
+    impl TerrainStage for RiverStage {
+        fn name(&self) -> &str {
+            return "rivers";
+        }
+
+        fn validate(&self, config: &GenerationConfig) -> Result<(), MapGenError> {
+            return self.apply(config).validate();
+        }
+
+        fn configure(&mut self, config: &GenerationConfig) {
+            *self = RiverStage::new(config);
+        }
+
+        fn settings(&self) -> Vec<(&str, String)> {
+            return vec![
+                (
+                    "peak_rainwater_collection_ratio",
+                    self.peak_rainwater_collection_ratio.to_string(),
+                ),
+                ("peak_elev_max", self.peak_elev_max.to_string()),
+                ("peak_elev_min", self.peak_elev_min.to_string()),
+                (
+                    "mid_rainwater_collection_ratio",
+                    self.mid_rainwater_collection_ratio.to_string(),
+                ),
+                ("mid_elev_max", self.mid_elev_max.to_string()),
+                ("mid_elev_min", self.mid_elev_min.to_string()),
+            ];
+        }
+
+        fn set_setting(&mut self, key: &str, value: &str) -> Result<(), MapGenError> {
+            let config = self.apply(&GenerationConfig::default());
+            return set_from_config(self, config, key, value);
+        }
+
+        fn run(
+            &self,
+            graph: &mut Graph,
+            config: &GenerationConfig,
+            rng: &mut StdRng,
+        ) -> Result<(), MapGenError> {
+            create_rivers(graph, &self.apply(config), rng)?;
+            return Ok(());
+        }
+    }
+
+    impl TerrainStage for BiomeStage {
+        fn name(&self) -> &str {
+            return "biomes";
+        }
+
+        fn validate(&self, config: &GenerationConfig) -> Result<(), MapGenError> {
+            return self.apply(config).validate();
+        }
+
+        fn configure(&mut self, config: &GenerationConfig) {
+            *self = BiomeStage::new(config);
+        }
+
+        fn settings(&self) -> Vec<(&str, String)> {
+            return vec![
+                ("moisture_falloff", self.moisture_falloff.to_string()),
+                (
+                    "moisture_elevation_weight",
+                    self.moisture_elevation_weight.to_string(),
+                ),
+            ];
+        }
+
+        fn set_setting(&mut self, key: &str, value: &str) -> Result<(), MapGenError> {
+            let config = self.apply(&GenerationConfig::default());
+            return set_from_config(self, config, key, value);
+        }
+
+        fn run(
+            &self,
+            graph: &mut Graph,
+            config: &GenerationConfig,
+            _rng: &mut StdRng,
+        ) -> Result<(), MapGenError> {
+            assign_biomes(graph, &self.apply(config))?;
+            return Ok(());
+        }
+    }
+
+    impl TerrainStage for EdgeDivisionStage {
+        fn name(&self) -> &str {
+            return "edge_divisions";
+        }
+
+        fn validate(&self, config: &GenerationConfig) -> Result<(), MapGenError> {
+            return self.apply(config).validate();
+        }
+
+        fn configure(&mut self, config: &GenerationConfig) {
+            *self = EdgeDivisionStage::new(config);
+        }
+
+        fn settings(&self) -> Vec<(&str, String)> {
+            return vec![("edge_subdivisions", self.edge_subdivisions.to_string())];
+        }
+
+        fn set_setting(&mut self, key: &str, value: &str) -> Result<(), MapGenError> {
+            let config = self.apply(&GenerationConfig::default());
+            return set_from_config(self, config, key, value);
+        }
+
+        fn run(
+            &self,
+            graph: &mut Graph,
+            config: &GenerationConfig,
+            _rng: &mut StdRng,
+        ) -> Result<(), MapGenError> {
+            add_edge_divisions(graph, &self.apply(config))?;
+            return Ok(());
+        }
+    }
+
+    /// The stages of `full_terrain_gen`, in the order they run, with their
+    /// settings taken from `config`
+    pub fn default_stages(config: &GenerationConfig) -> Vec<Box<dyn TerrainStage>> {
+        return vec![
+            Box::new(IslandStage::new(config)),
+            Box::new(OceanStage),
+            Box::new(CoastalStage),
+            Box::new(ElevationStage),
+            Box::new(RiverStage::new(config)),
+            Box::new(BiomeStage::new(config)),
+            Box::new(EdgeDivisionStage::new(config)),
+        ];
+    }
+}
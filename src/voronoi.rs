@@ -1,9 +1,38 @@
 pub mod voronoi {
     use rand::Rng;
-    use voronator::{delaunator, VoronoiDiagram};
+    use voronator::{delaunator, polygon::Polygon, VoronoiDiagram};
 
-    fn initialise(i: usize, x_scale: f64, y_scale: f64) -> Vec<(f64, f64)> {
-        let mut rng = rand::thread_rng();
+    use crate::{config::config::GenerationConfig, error::error::MapGenError};
+
+    /// How the voronoi sites are laid out before any relaxation is applied.
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum PointDistribution {
+        /// Independent uniform random points
+        Uniform,
+        /// Bridson's Poisson-disk sampling, with the minimum spacing chosen so
+        /// that roughly `cell_count` points fit in the map
+        PoissonDisk,
+        /// A square grid with each point moved by up to `jitter` of the grid
+        /// spacing. A jitter of 0 is rejected as it leaves four sites on every circle
+        JitteredGrid { jitter: f64 },
+        /// Offset rows of points, giving hexagonal cells
+        HexGrid,
+        /// Points supplied by the caller, `cell_count` is ignored
+        Custom(Vec<(f64, f64)>),
+    }
+
+    /// Summary of the Lloyd relaxation applied to the voronoi sites.
+    #[derive(Debug, Clone, PartialEq, Default)]
+    pub struct RelaxationReport {
+        /// Number of relaxation passes actually run
+        pub iterations: usize,
+        /// Furthest any site moved during the last pass
+        pub max_displacement: f64,
+        /// Variance of the cell areas over the squared mean area, 0 when every cell is equal
+        pub area_variance: f64,
+    }
+
+    fn initialise(i: usize, x_scale: f64, y_scale: f64, rng: &mut impl Rng) -> Vec<(f64, f64)> {
         let mut output: Vec<(f64, f64)> = Vec::new();
         for _i in 0..i {
             output.push((rng.gen::<f64>() * x_scale, rng.gen::<f64>() * y_scale));
@@ -11,55 +40,430 @@ pub mod voronoi {
         return output;
     }
 
-    fn relax_diagram(
-        diagram: VoronoiDiagram<delaunator::Point>,
+    // Bridson, "Fast Poisson Disk Sampling in Arbitrary Dimensions" (2007)
+    fn initialise_poisson_disk(
+        i: usize,
+        x_scale: f64,
+        y_scale: f64,
+        rng: &mut impl Rng,
+    ) -> Vec<(f64, f64)> {
+        const ATTEMPTS: usize = 30;
+        // a maximal Bridson sample covers the area at about 0.65 / r^2 points
+        let radius = (0.65 * x_scale * y_scale / i as f64).sqrt();
+        let grid_size = radius / 2.0_f64.sqrt();
+        let grid_w = (x_scale / grid_size).ceil() as usize;
+        let grid_h = (y_scale / grid_size).ceil() as usize;
+        let grid_index = |(x, y): (f64, f64)| {
+            let gx = ((x / grid_size) as usize).min(grid_w - 1);
+            let gy = ((y / grid_size) as usize).min(grid_h - 1);
+            return (gx, gy);
+        };
+        let mut grid: Vec<Option<usize>> = vec![None; grid_w * grid_h];
+
+        let mut output: Vec<(f64, f64)> = Vec::new();
+        let first = (rng.gen::<f64>() * x_scale, rng.gen::<f64>() * y_scale);
+        let (gx, gy) = grid_index(first);
+        grid[gy * grid_w + gx] = Some(0);
+        output.push(first);
+        let mut active: Vec<usize> = vec![0];
+
+        while !active.is_empty() {
+            let active_index = rng.gen_range(0..active.len());
+            let (px, py) = output[active[active_index]];
+            let mut found = false;
+            for _attempt in 0..ATTEMPTS {
+                let angle = rng.gen::<f64>() * std::f64::consts::TAU;
+                let distance = radius * (1.0 + rng.gen::<f64>());
+                let candidate = (px + distance * angle.cos(), py + distance * angle.sin());
+                if candidate.0 < 0.0
+                    || candidate.0 >= x_scale
+                    || candidate.1 < 0.0
+                    || candidate.1 >= y_scale
+                {
+                    continue;
+                }
+                let (cx, cy) = grid_index(candidate);
+                let too_close = (cy.saturating_sub(2)..(cy + 3).min(grid_h)).any(|ny| {
+                    (cx.saturating_sub(2)..(cx + 3).min(grid_w)).any(|nx| {
+                        if let Some(n) = grid[ny * grid_w + nx] {
+                            let (ox, oy) = output[n];
+                            return (ox - candidate.0).powi(2) + (oy - candidate.1).powi(2)
+                                < radius * radius;
+                        }
+                        return false;
+                    })
+                });
+                if !too_close {
+                    grid[cy * grid_w + cx] = Some(output.len());
+                    active.push(output.len());
+                    output.push(candidate);
+                    found = true;
+                    break;
+                }
+            }
+            if !found {
+                active.swap_remove(active_index);
+            }
+        }
+        return output;
+    }
+
+    fn initialise_jittered_grid(
+        i: usize,
+        x_scale: f64,
+        y_scale: f64,
+        jitter: f64,
+        rng: &mut impl Rng,
+    ) -> Vec<(f64, f64)> {
+        let columns = ((i as f64 * x_scale / y_scale).sqrt().round() as usize).max(1);
+        let rows = ((i as f64 / columns as f64).round() as usize).max(1);
+        let dx = x_scale / columns as f64;
+        let dy = y_scale / rows as f64;
+        let mut output: Vec<(f64, f64)> = Vec::with_capacity(columns * rows);
+        for row in 0..rows {
+            for column in 0..columns {
+                // offsets stay inside the point's own grid square, so points never cross
+                let ox = (rng.gen::<f64>() - 0.5) * jitter * dx;
+                let oy = (rng.gen::<f64>() - 0.5) * jitter * dy;
+                output.push((
+                    (column as f64 + 0.5) * dx + ox,
+                    (row as f64 + 0.5) * dy + oy,
+                ));
+            }
+        }
+        return output;
+    }
+
+    fn initialise_hex_grid(i: usize, x_scale: f64, y_scale: f64) -> Vec<(f64, f64)> {
+        // each point of a hex lattice with spacing d covers d^2 * sqrt(3) / 2
+        let spacing = (2.0 * x_scale * y_scale / (3.0_f64.sqrt() * i as f64)).sqrt();
+        let row_height = spacing * 3.0_f64.sqrt() / 2.0;
+        let columns = ((x_scale / spacing).floor() as usize).max(1);
+        let rows = ((y_scale / row_height).floor() as usize).max(1);
+        // centre the lattice so the border cells are the same size on each side
+        let x_margin = (x_scale - (columns as f64 - 0.5) * spacing) / 2.0;
+        let y_margin = (y_scale - (rows as f64 - 1.0) * row_height) / 2.0;
+        let mut output: Vec<(f64, f64)> = Vec::with_capacity(columns * rows);
+        for row in 0..rows {
+            let row_offset = if row % 2 == 1 { spacing / 2.0 } else { 0.0 };
+            for column in 0..columns {
+                output.push((
+                    x_margin + row_offset + column as f64 * spacing,
+                    y_margin + row as f64 * row_height,
+                ));
+            }
+        }
+        return output;
+    }
+
+    fn generate_points(
+        distribution: &PointDistribution,
+        i: usize,
         x_scale: f64,
         y_scale: f64,
-    ) -> VoronoiDiagram<delaunator::Point> {
-        let i_1_points: Vec<(f64, f64)> = diagram
-            .cells()
+        rng: &mut impl Rng,
+    ) -> Vec<(f64, f64)> {
+        return match distribution {
+            PointDistribution::Uniform => initialise(i, x_scale, y_scale, rng),
+            PointDistribution::PoissonDisk => initialise_poisson_disk(i, x_scale, y_scale, rng),
+            PointDistribution::JitteredGrid { jitter } => {
+                initialise_jittered_grid(i, x_scale, y_scale, *jitter, rng)
+            }
+            PointDistribution::HexGrid => initialise_hex_grid(i, x_scale, y_scale),
+            PointDistribution::Custom(points) => points.clone(),
+        };
+    }
+
+    fn build_diagram(
+        points: &[(f64, f64)],
+        x_scale: f64,
+        y_scale: f64,
+        wrap_x: bool,
+    ) -> Result<VoronoiDiagram<delaunator::Point>, MapGenError> {
+        let diagram = if wrap_x {
+            // surround the sites with copies shifted a map width east and west,
+            // so the cells along the seam are shaped by their neighbours across it
+            let mut sites = points.to_vec();
+            sites.extend(points.iter().map(|(x, y)| (x - x_scale, *y)));
+            sites.extend(points.iter().map(|(x, y)| (x + x_scale, *y)));
+            VoronoiDiagram::<delaunator::Point>::from_tuple(
+                &(-x_scale, 0.0),
+                &(2.0 * x_scale, y_scale),
+                &sites,
+            )
+        } else {
+            VoronoiDiagram::<delaunator::Point>::from_tuple(
+                &(0.0, 0.0),
+                &(x_scale, y_scale),
+                points,
+            )
+        };
+        return diagram.ok_or_else(|| {
+            MapGenError::DegeneratePoints(format!(
+                "no triangulation for {} points, they may be collinear or duplicated",
+                points.len()
+            ))
+        });
+    }
+
+    /// The cells of the sites that were asked for, leaving out the shifted
+    /// copies a wrapping diagram is built with.
+    pub fn primary_cells(
+        diagram: &VoronoiDiagram<delaunator::Point>,
+        wrap_x: bool,
+    ) -> &[Polygon<delaunator::Point>] {
+        // cells() already leaves out the helper sites voronator adds at the bounds
+        let site_count = if wrap_x {
+            diagram.cells().len() / 3
+        } else {
+            diagram.cells().len()
+        };
+        return &diagram.cells()[..site_count];
+    }
+
+    // returns the signed area and area centroid of a polygon, falling back to
+    // the vertex average for slivers too thin to divide by
+    fn polygon_area_centroid(points: &[delaunator::Point]) -> (f64, (f64, f64)) {
+        let mut area = 0.0;
+        let mut cx = 0.0;
+        let mut cy = 0.0;
+        for (i, p) in points.iter().enumerate() {
+            let q = &points[(i + 1) % points.len()];
+            let cross = p.x * q.y - q.x * p.y;
+            area += cross;
+            cx += (p.x + q.x) * cross;
+            cy += (p.y + q.y) * cross;
+        }
+        area /= 2.0;
+        if area.abs() < f64::EPSILON {
+            let l = points.len() as f64;
+            let average = points.iter().fold((0.0, 0.0), |(x, y), p| {
+                return (x + (p.x / l), y + (p.y / l));
+            });
+            return (area, average);
+        }
+        return (area, (cx / (6.0 * area), cy / (6.0 * area)));
+    }
+
+    fn cell_area_variance(diagram: &VoronoiDiagram<delaunator::Point>, wrap_x: bool) -> f64 {
+        let areas: Vec<f64> = primary_cells(diagram, wrap_x)
+            .iter()
+            .filter(|cell| !cell.points().is_empty())
+            .map(|cell| polygon_area_centroid(cell.points()).0.abs())
+            .collect();
+        if areas.is_empty() {
+            return 0.0;
+        }
+        let mean = areas.iter().sum::<f64>() / areas.len() as f64;
+        let variance = areas.iter().map(|a| (a - mean).powi(2)).sum::<f64>() / areas.len() as f64;
+        return variance / (mean * mean);
+    }
+
+    // moves every site to the area centroid of its cell, returning the new
+    // diagram and the furthest any site moved. A site whose cell came out
+    // empty stays where it is, so no site is ever lost
+    fn relax_diagram(
+        diagram: &VoronoiDiagram<delaunator::Point>,
+        config: &GenerationConfig,
+    ) -> Result<(VoronoiDiagram<delaunator::Point>, f64), MapGenError> {
+        let mut max_displacement: f64 = 0.0;
+        let i_1_points: Vec<(f64, f64)> = primary_cells(diagram, config.wrap_x)
             .iter()
-            .map(|cell| {
-                let l = cell.points().len() as f64;
-                cell.points().iter().fold((0.0, 0.0), |(x, y), p| {
-                    return (x + (p.x / l), y + (p.y / l));
-                })
+            .zip(&diagram.sites)
+            .map(|(cell, site)| {
+                if cell.points().is_empty() {
+                    return (site.x, site.y);
+                }
+                let (_area, centroid) = polygon_area_centroid(cell.points());
+                let displacement =
+                    ((centroid.0 - site.x).powi(2) + (centroid.1 - site.y).powi(2)).sqrt();
+                max_displacement = max_displacement.max(displacement);
+                if config.wrap_x {
+                    return (centroid.0.rem_euclid(config.x_scale), centroid.1);
+                }
+                return centroid;
             })
             .collect();
-        return VoronoiDiagram::<delaunator::Point>::from_tuple(
-            &(0.0, 0.0),
-            &(x_scale, y_scale),
-            &i_1_points,
-        )
-        .unwrap();
+        return Ok((
+            build_diagram(&i_1_points, config.x_scale, config.y_scale, config.wrap_x)?,
+            max_displacement,
+        ));
     }
 
+    // runs up to `relax_factor` passes, stopping early once no site moves
+    // further than `relax_tolerance` of the mean site spacing
     fn relax_diagram_n(
         diagram: VoronoiDiagram<delaunator::Point>,
-        x_scale: f64,
-        y_scale: f64,
-        i: usize,
-    ) -> VoronoiDiagram<delaunator::Point> {
+        config: &GenerationConfig,
+    ) -> Result<(VoronoiDiagram<delaunator::Point>, RelaxationReport), MapGenError> {
+        let site_count = primary_cells(&diagram, config.wrap_x).len() as f64;
+        let spacing = (config.x_scale * config.y_scale / site_count).sqrt();
         let mut base = diagram;
-        for _i in 0..i {
-            base = relax_diagram(base, x_scale, y_scale);
+        let mut iterations = 0;
+        let mut max_displacement = 0.0;
+        while iterations < config.relax_factor {
+            let (relaxed, displacement) = relax_diagram(&base, config)?;
+            base = relaxed;
+            iterations += 1;
+            max_displacement = displacement;
+            if displacement <= config.relax_tolerance * spacing {
+                break;
+            }
         }
-        return base;
+        let area_variance = cell_area_variance(&base, config.wrap_x);
+        return Ok((
+            base,
+            RelaxationReport {
+                iterations,
+                max_displacement,
+                area_variance,
+            },
+        ));
     }
 
+    /// Builds the voronoi diagram for `config`. On wrapping maps the diagram
+    /// also holds shifted copies of every site, see `primary_cells`.
     pub fn initialise_voronoi(
-        i: usize,
-        x_scale: f64,
-        y_scale: f64,
-        relax_factor: usize,
-    ) -> VoronoiDiagram<delaunator::Point> {
-        let base = VoronoiDiagram::<delaunator::Point>::from_tuple(
-            &(0.0, 0.0),
-            &(x_scale, y_scale),
-            &initialise(i, x_scale, y_scale),
-        )
-        .unwrap();
-
-        return relax_diagram_n(base, x_scale, y_scale, relax_factor);
+        config: &GenerationConfig,
+        rng: &mut impl Rng,
+    ) -> Result<(VoronoiDiagram<delaunator::Point>, RelaxationReport), MapGenError> {
+        let points = generate_points(
+            &config.point_distribution,
+            config.cell_count,
+            config.x_scale,
+            config.y_scale,
+            rng,
+        );
+        let base = build_diagram(&points, config.x_scale, config.y_scale, config.wrap_x)?;
+
+        return relax_diagram_n(base, config);
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        use super::*;
+
+        fn in_bounds(points: &[(f64, f64)], x_scale: f64, y_scale: f64) -> bool {
+            return points
+                .iter()
+                .all(|(x, y)| (0.0..=x_scale).contains(x) && (0.0..=y_scale).contains(y));
+        }
+
+        #[test]
+        fn poisson_disk_spacing_test() {
+            let points = initialise_poisson_disk(400, 400.0, 200.0, &mut StdRng::seed_from_u64(1));
+            let radius = (0.65 * 400.0 * 200.0 / 400.0_f64).sqrt();
+            assert!(in_bounds(&points, 400.0, 200.0));
+            assert!(points.len() > 340 && points.len() < 460);
+            for (a_i, a) in points.iter().enumerate() {
+                for b in &points[a_i + 1..] {
+                    let d = ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt();
+                    assert!(d >= radius);
+                }
+            }
+        }
+
+        #[test]
+        fn grid_distributions_fill_bounds_test() {
+            let mut rng = StdRng::seed_from_u64(2);
+            let square = initialise_jittered_grid(500, 400.0, 200.0, 0.5, &mut rng);
+            let hex = initialise_hex_grid(500, 400.0, 200.0);
+            assert!(in_bounds(&square, 400.0, 200.0));
+            assert!(in_bounds(&hex, 400.0, 200.0));
+            assert!(square.len() > 450 && square.len() < 550);
+            assert!(hex.len() > 400 && hex.len() < 600);
+        }
+
+        #[test]
+        fn polygon_area_centroid_test() {
+            // an L shape, whose vertex average (1.0, 1.0) sits off the true centroid
+            let points: Vec<delaunator::Point> = [
+                (0.0, 0.0),
+                (3.0, 0.0),
+                (3.0, 1.0),
+                (1.0, 1.0),
+                (1.0, 3.0),
+                (0.0, 3.0),
+            ]
+            .iter()
+            .map(|(x, y)| delaunator::Point { x: *x, y: *y })
+            .collect();
+            let (area, (cx, cy)) = polygon_area_centroid(&points);
+            assert!((area - 5.0).abs() < 1e-9);
+            assert!((cx - 1.1).abs() < 1e-9 && (cy - 1.1).abs() < 1e-9);
+        }
+
+        fn config(relax_factor: usize, relax_tolerance: f64) -> GenerationConfig {
+            return GenerationConfig {
+                cell_count: 400,
+                x_scale: 200.0,
+                y_scale: 200.0,
+                relax_factor,
+                relax_tolerance,
+                ..Default::default()
+            };
+        }
+
+        #[test]
+        fn relaxation_evens_cells_and_stops_early_test() {
+            // each run starts from the same sites, so only the relaxation differs
+            let run = |config: GenerationConfig| {
+                return initialise_voronoi(&config, &mut StdRng::seed_from_u64(3))
+                    .unwrap()
+                    .1;
+            };
+            let unrelaxed = run(config(0, 0.0));
+            let relaxed = run(config(10, 0.0));
+            let loose = run(config(10, 10.0));
+            assert_eq!(unrelaxed.iterations, 0);
+            assert_eq!(relaxed.iterations, 10);
+            assert!(relaxed.area_variance < unrelaxed.area_variance);
+            assert_eq!(loose.iterations, 1);
+        }
+
+        #[test]
+        fn relaxation_keeps_every_site_test() {
+            // the repeated site gets an empty cell from the triangulation
+            let mut points = initialise_hex_grid(100, 100.0, 100.0);
+            points.push(points[40]);
+            let config = GenerationConfig {
+                x_scale: 100.0,
+                y_scale: 100.0,
+                relax_factor: 5,
+                relax_tolerance: 0.0,
+                point_distribution: PointDistribution::Custom(points.clone()),
+                ..Default::default()
+            };
+            let (diagram, report) =
+                initialise_voronoi(&config, &mut StdRng::seed_from_u64(4)).unwrap();
+            assert_eq!(report.iterations, 5);
+            assert_eq!(primary_cells(&diagram, false).len(), points.len());
+        }
+
+        #[test]
+        fn every_distribution_builds_a_diagram_test() {
+            let distributions = [
+                PointDistribution::Uniform,
+                PointDistribution::PoissonDisk,
+                PointDistribution::JitteredGrid { jitter: 0.8 },
+                PointDistribution::HexGrid,
+                PointDistribution::Custom(vec![(10.0, 10.0), (90.0, 20.0), (50.0, 80.0)]),
+            ];
+            for point_distribution in distributions {
+                let config = GenerationConfig {
+                    cell_count: 300,
+                    x_scale: 100.0,
+                    y_scale: 100.0,
+                    relax_factor: 0,
+                    point_distribution,
+                    ..Default::default()
+                };
+                let (diagram, _report) =
+                    initialise_voronoi(&config, &mut StdRng::seed_from_u64(5)).unwrap();
+                assert!(!diagram.cells().is_empty());
+            }
+        }
     }
 }